@@ -0,0 +1,324 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use core::{mem, ptr, slice};
+use std::ffi;
+use std::prelude::*;
+use std::proto::Protocol;
+
+// TODO: Move to uefi library {
+pub const DEVICE_PATH_PROTOCOL_GUID: Guid = guid!("09576e91-6d3f-11d2-8e39-00a0c969723b");
+pub const DEVICE_PATH_TO_TEXT_PROTOCOL_GUID: Guid = guid!("8b843e20-8132-4852-90cc-551a4e4a7f1c");
+pub const GLOBAL_VARIABLE_GUID: Guid = guid!("8be4df61-93ca-11d2-aa0d-00e098032b8c");
+
+pub const LOAD_OPTION_ACTIVE: u32 = 0x0000_0001;
+
+const DEVICE_PATH_HARDWARE: u8 = 0x01;
+const DEVICE_PATH_ACPI: u8 = 0x02;
+const DEVICE_PATH_MESSAGING: u8 = 0x03;
+const DEVICE_PATH_MEDIA: u8 = 0x04;
+const DEVICE_PATH_END: u8 = 0x7F;
+
+const DEVICE_PATH_MESSAGING_USB: u8 = 0x05;
+const DEVICE_PATH_MESSAGING_MAC: u8 = 0x0B;
+const DEVICE_PATH_MESSAGING_IPV4: u8 = 0x0C;
+const DEVICE_PATH_MESSAGING_IPV6: u8 = 0x0D;
+const DEVICE_PATH_MESSAGING_USB_CLASS: u8 = 0x0F;
+const DEVICE_PATH_MESSAGING_SATA: u8 = 0x12;
+const DEVICE_PATH_MESSAGING_NVME: u8 = 0x17;
+const DEVICE_PATH_MESSAGING_URI: u8 = 0x18;
+const DEVICE_PATH_MEDIA_HARD_DRIVE: u8 = 0x01;
+
+#[repr(C, packed)]
+pub struct DevicePath {
+    pub Type: u8,
+    pub SubType: u8,
+    pub Length: [u8; 2],
+}
+
+pub struct DevicePathProtocol(pub &'static mut DevicePath);
+
+impl Protocol<DevicePath> for DevicePathProtocol {
+    fn guid() -> Guid {
+        DEVICE_PATH_PROTOCOL_GUID
+    }
+
+    fn new(inner: &'static mut DevicePath) -> Self {
+        DevicePathProtocol(inner)
+    }
+}
+
+#[repr(C)]
+pub struct DevicePathToTextProtocol {
+    pub ConvertDeviceNodeToText: extern "efiapi" fn(
+        DeviceNode: *const DevicePath,
+        DisplayOnly: bool,
+        AllowShortcuts: bool,
+    ) -> *mut u16,
+    pub ConvertDevicePathToText: extern "efiapi" fn(
+        DevicePath: *const DevicePath,
+        DisplayOnly: bool,
+        AllowShortcuts: bool,
+    ) -> *mut u16,
+}
+
+pub struct DevicePathToText(pub &'static mut DevicePathToTextProtocol);
+
+impl Protocol<DevicePathToTextProtocol> for DevicePathToText {
+    fn guid() -> Guid {
+        DEVICE_PATH_TO_TEXT_PROTOCOL_GUID
+    }
+
+    fn new(inner: &'static mut DevicePathToTextProtocol) -> Self {
+        DevicePathToText(inner)
+    }
+}
+// } TODO: Move to uefi library
+
+/// Split a device path into its nodes, stopping at the end of the entire path
+fn device_path_nodes(data: &[u8]) -> Vec<&[u8]> {
+    let mut nodes = Vec::new();
+    let mut offset = 0;
+    while offset + mem::size_of::<DevicePath>() <= data.len() {
+        let kind = data[offset];
+        let len = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        if kind == DEVICE_PATH_END || len < mem::size_of::<DevicePath>() {
+            break;
+        }
+        let Some(node) = data.get(offset..offset + len) else {
+            break;
+        };
+        nodes.push(node);
+        offset += len;
+    }
+    nodes
+}
+
+/// Bytes of a device path provided by firmware, not including the end node
+unsafe fn device_path_data(device_path: *const DevicePath) -> &'static [u8] {
+    let start = device_path as *const u8;
+    let mut offset = 0;
+    loop {
+        let node = unsafe { start.add(offset) };
+        let (kind, len) = unsafe {
+            (
+                *node,
+                u16::from_le_bytes([*node.add(2), *node.add(3)]) as usize,
+            )
+        };
+        if kind == DEVICE_PATH_END || len < mem::size_of::<DevicePath>() {
+            break;
+        }
+        offset += len;
+    }
+    unsafe { slice::from_raw_parts(start, offset) }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeviceKind {
+    Nvme,
+    Sata,
+    Usb,
+    Network,
+    Other,
+}
+
+impl DeviceKind {
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Self::Nvme => Some("NVMe"),
+            Self::Sata => Some("SATA"),
+            Self::Usb => Some("USB"),
+            Self::Network => Some("Network"),
+            Self::Other => None,
+        }
+    }
+}
+
+/// A `Boot####` load option
+pub struct LoadOption {
+    pub attributes: u32,
+    pub device_path: Vec<u8>,
+    // Variable attributes and contents, written back with the load option attributes
+    variable_attributes: u32,
+    data: Vec<u8>,
+}
+
+impl LoadOption {
+    fn variable_name(number: u16) -> Vec<u16> {
+        format!("Boot{number:04X}")
+            .encode_utf16()
+            .chain(Some(0))
+            .collect()
+    }
+
+    /// Read the `Boot####` variable with the given option number
+    pub fn read(number: u16) -> Result<Self> {
+        let uefi = std::system_table();
+
+        let name = Self::variable_name(number);
+
+        let mut size = 0;
+        let status = (uefi.RuntimeServices.GetVariable)(
+            name.as_ptr(),
+            &GLOBAL_VARIABLE_GUID,
+            ptr::null_mut(),
+            &mut size,
+            ptr::null_mut(),
+        );
+        if status != Status::BUFFER_TOO_SMALL {
+            return Err(if status.is_success() {
+                Status::NOT_FOUND
+            } else {
+                status
+            });
+        }
+
+        let mut data = vec![0u8; size];
+        let mut variable_attributes = 0;
+        Result::from((uefi.RuntimeServices.GetVariable)(
+            name.as_ptr(),
+            &GLOBAL_VARIABLE_GUID,
+            &mut variable_attributes,
+            &mut size,
+            data.as_mut_ptr(),
+        ))?;
+        data.truncate(size);
+
+        Self::parse(data, variable_attributes).ok_or(Status::INVALID_PARAMETER)
+    }
+
+    /// Write the `Boot####` variable with the given option number, updating its attributes
+    pub fn write(&self, number: u16) -> Result<()> {
+        let uefi = std::system_table();
+
+        let name = Self::variable_name(number);
+
+        let mut data = self.data.clone();
+        data[0..4].copy_from_slice(&self.attributes.to_le_bytes());
+        Result::from((uefi.RuntimeServices.SetVariable)(
+            name.as_ptr(),
+            &GLOBAL_VARIABLE_GUID,
+            self.variable_attributes,
+            data.len(),
+            data.as_ptr(),
+        ))?;
+        Ok(())
+    }
+
+    /// Parse an `EFI_LOAD_OPTION`
+    fn parse(data: Vec<u8>, variable_attributes: u32) -> Option<Self> {
+        let attributes = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+        let file_path_len = u16::from_le_bytes(data.get(4..6)?.try_into().ok()?) as usize;
+
+        // Skip the description, which is already shown by the option prompt
+        let mut offset = 6;
+        loop {
+            let w = u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?);
+            offset += 2;
+            if w == 0 {
+                break;
+            }
+        }
+
+        let device_path = data.get(offset..offset + file_path_len)?.to_vec();
+
+        Some(Self {
+            attributes,
+            device_path,
+            variable_attributes,
+            data,
+        })
+    }
+
+    pub fn active(&self) -> bool {
+        self.attributes & LOAD_OPTION_ACTIVE != 0
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        if active {
+            self.attributes |= LOAD_OPTION_ACTIVE;
+        } else {
+            self.attributes &= !LOAD_OPTION_ACTIVE;
+        }
+    }
+
+    /// Type of device the option boots from, based on its device path
+    pub fn device_kind(&self) -> DeviceKind {
+        for node in device_path_nodes(&self.device_path) {
+            if node[0] != DEVICE_PATH_MESSAGING {
+                continue;
+            }
+            match node[1] {
+                DEVICE_PATH_MESSAGING_NVME => return DeviceKind::Nvme,
+                DEVICE_PATH_MESSAGING_SATA => return DeviceKind::Sata,
+                DEVICE_PATH_MESSAGING_USB | DEVICE_PATH_MESSAGING_USB_CLASS => {
+                    return DeviceKind::Usb;
+                }
+                DEVICE_PATH_MESSAGING_MAC
+                | DEVICE_PATH_MESSAGING_IPV4
+                | DEVICE_PATH_MESSAGING_IPV6
+                | DEVICE_PATH_MESSAGING_URI => return DeviceKind::Network,
+                _ => (),
+            }
+        }
+        DeviceKind::Other
+    }
+
+    /// Convert the device path to text, using shortcuts if the firmware supports them
+    pub fn device_path_text(&self, to_text: &DevicePathToText) -> Option<String> {
+        let nodes = device_path_nodes(&self.device_path);
+        if nodes.is_empty() {
+            return None;
+        }
+
+        // Terminate the first path with an end node
+        let mut data = Vec::new();
+        for node in nodes {
+            data.extend_from_slice(node);
+        }
+        data.extend_from_slice(&[DEVICE_PATH_END, 0xFF, 4, 0]);
+
+        let text_ptr =
+            (to_text.0.ConvertDevicePathToText)(data.as_ptr() as *const DevicePath, false, true);
+        if text_ptr.is_null() {
+            return None;
+        }
+        let text = ffi::nstr(text_ptr);
+
+        let uefi = std::system_table();
+        let _ = (uefi.BootServices.FreePool)(text_ptr as usize);
+
+        Some(text)
+    }
+
+    /// Check if the device the option boots from is connected. Short-form paths expanded by
+    /// the boot manager cannot be checked and are assumed present.
+    pub fn device_present(&self, device_paths: &[DevicePathProtocol]) -> bool {
+        let nodes = device_path_nodes(&self.device_path);
+        let Some(first) = nodes.first() else {
+            return false;
+        };
+        let short_form = match (first[0], first[1]) {
+            (DEVICE_PATH_MEDIA, DEVICE_PATH_MEDIA_HARD_DRIVE) => true,
+            (DEVICE_PATH_MESSAGING, DEVICE_PATH_MESSAGING_USB_CLASS) => true,
+            (DEVICE_PATH_MESSAGING, DEVICE_PATH_MESSAGING_URI) => true,
+            (kind, _) => kind != DEVICE_PATH_HARDWARE && kind != DEVICE_PATH_ACPI,
+        };
+        if short_form {
+            return true;
+        }
+
+        // Like LocateDevicePath, find a device that matches the path up to a media node
+        for device_path in device_paths.iter() {
+            let data = unsafe { device_path_data(device_path.0) };
+            if data.is_empty() || !self.device_path.starts_with(data) {
+                continue;
+            }
+            match self.device_path.get(data.len()) {
+                None | Some(&DEVICE_PATH_END) | Some(&DEVICE_PATH_MEDIA) => return true,
+                _ => (),
+            }
+        }
+        false
+    }
+}
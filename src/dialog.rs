@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use core::cmp;
+use orbclient::Renderer;
+use std::prelude::*;
+
+use crate::display::Display;
+use crate::key::{Key, Modifiers, key_modifiers};
+use crate::ui::Ui;
+
+/// Show a message on top of the current screen until it is acknowledged, or until the
+/// optional timeout in seconds expires
+pub fn message(
+    display: &mut Display,
+    ui: &Ui,
+    message: &str,
+    timeout_opt: Option<u32>,
+) -> Result<()> {
+    dialog(display, ui, message, &["OK"], timeout_opt)?;
+    Ok(())
+}
+
+/// Ask to choose one of the buttons, returning None if canceled with Escape
+pub fn choice(
+    display: &mut Display,
+    ui: &Ui,
+    message: &str,
+    buttons: &[&str],
+) -> Result<Option<usize>> {
+    dialog(display, ui, message, buttons, None)
+}
+
+fn dialog(
+    display: &mut Display,
+    ui: &Ui,
+    message: &str,
+    buttons: &[&str],
+    timeout_opt: Option<u32>,
+) -> Result<Option<usize>> {
+    let (display_w, display_h) = (display.width(), display.height());
+
+    let scale: i32 = if display_h > 1440 {
+        4
+    } else if display_h > 720 {
+        2
+    } else {
+        1
+    };
+
+    // Style {
+    let margin_lr = 16 * scale;
+    let margin_tb = 8 * scale;
+
+    let dialog_w = cmp::min(480 * scale as u32, display_w - margin_lr as u32 * 4);
+
+    let font_size = (16 * scale) as f32;
+    // } Style
+
+    let texts = ui.render_text_wrapped(message, font_size, dialog_w);
+
+    // Message lines, a blank line, and the buttons
+    let dialog_h = (texts.len() as u32 + 2) * font_size as u32;
+    let x = (display_w as i32 - dialog_w as i32) / 2;
+
+    let mut selected = 0;
+    let mut remaining_opt = timeout_opt;
+    loop {
+        let mut y = (display_h as i32 - dialog_h as i32) / 2;
+
+        display.rect(
+            x - margin_lr,
+            y - margin_tb,
+            dialog_w + margin_lr as u32 * 2,
+            dialog_h + margin_tb as u32 * 2,
+            ui.background_color,
+        );
+        ui.draw_pretty_box(display, x, y, dialog_w, dialog_h, false);
+
+        for text in texts.iter() {
+            text.draw(display, x, y, ui.text_color);
+            y += font_size as i32;
+        }
+        y += font_size as i32;
+
+        let mut rendered_buttons = Vec::with_capacity(buttons.len());
+        for (i, button) in buttons.iter().enumerate() {
+            let rendered = match remaining_opt {
+                Some(remaining) if i == selected => {
+                    ui.render(&format!("{button} ({remaining})"), font_size)
+                }
+                _ => ui.render(button, font_size),
+            };
+            rendered_buttons.push(rendered);
+        }
+
+        let buttons_w = rendered_buttons
+            .iter()
+            .map(|rendered| rendered.width() as i32 + margin_lr)
+            .sum::<i32>()
+            - margin_lr;
+        let mut button_x = x + (dialog_w as i32 - buttons_w) / 2;
+        for (i, rendered) in rendered_buttons.iter().enumerate() {
+            ui.draw_text_box(display, button_x, y, rendered, true, i == selected);
+            button_x += rendered.width() as i32 + margin_lr;
+        }
+
+        display.sync();
+
+        let k = match remaining_opt {
+            Some(ref mut remaining) => {
+                // Poll for keys until the next second has elapsed
+                let mut key_opt = None;
+                for _ in 0..100 {
+                    match key_modifiers(false) {
+                        Ok(k) => {
+                            key_opt = Some(k);
+                            break;
+                        }
+                        Err(Status::NOT_READY) => (),
+                        Err(err) => return Err(err),
+                    }
+                    let _ = (std::system_table().BootServices.Stall)(10_000);
+                }
+
+                match key_opt {
+                    Some(k) => k,
+                    None => {
+                        *remaining = remaining.saturating_sub(1);
+                        if *remaining == 0 {
+                            return Ok(None);
+                        }
+                        continue;
+                    }
+                }
+            }
+            None => key_modifiers(true)?,
+        };
+
+        match k {
+            (Key::Enter, _) => return Ok(Some(selected)),
+            (Key::Escape, _) => return Ok(None),
+            (Key::Left | Key::Up, _) | (Key::Tab, Modifiers { shift: true, .. }) => {
+                selected = selected.saturating_sub(1);
+            }
+            (Key::Right | Key::Down | Key::Tab, _) => {
+                if selected + 1 < buttons.len() {
+                    selected += 1;
+                }
+            }
+            (Key::Character(c), _) => {
+                // Select a button by its first letter
+                let c = c.to_ascii_lowercase();
+                if let Some(i) = buttons.iter().position(|button| {
+                    button.chars().next().map(|b| b.to_ascii_lowercase()) == Some(c)
+                }) {
+                    return Ok(Some(i));
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
-use core::{char, cmp, mem, ptr, slice};
+use core::cell::{Cell, Ref, RefCell};
+use core::{char, cmp, iter, mem, ops, ptr, slice};
 use orbclient::{Color, Renderer};
 use orbfont::Text;
 use std::ffi;
@@ -8,14 +9,30 @@ use std::prelude::*;
 use std::proto::Protocol;
 use std::uefi::hii::database::HiiHandle;
 use std::uefi::hii::ifr::{
-    HiiValue, IfrAction, IfrCheckbox, IfrNumeric, IfrOneOf, IfrOneOfOption, IfrOpCode, IfrOpHeader,
-    IfrOrderedList, IfrRef, IfrStatementHeader, IfrSubtitle, IfrTypeValueEnum,
+    HiiDate, HiiTime, HiiValue, IfrAction, IfrCheckbox, IfrNumeric, IfrOneOf, IfrOneOfOption,
+    IfrOpCode, IfrOpHeader, IfrOrderedList, IfrRef, IfrStatementHeader, IfrSubtitle,
+    IfrTypeValueEnum,
 };
-use std::uefi::hii::{AnimationId, ImageId, StringId};
+use std::uefi::hii::{AnimationId, ImageId, QuestionId, StringId, VarStoreId};
+use std::uefi::memory::MemoryType;
 use std::uefi::text::TextInputKey;
+use std::uefi::time::Time;
 
+use crate::boot::{DeviceKind, DevicePathProtocol, DevicePathToText, LoadOption};
+use crate::dialog;
 use crate::display::{Display, Output};
-use crate::key::{Key, raw_key};
+#[cfg(feature = "frame_time")]
+use crate::frame_time::FrameTimer;
+use crate::ifr::{
+    IFR_DISPLAY, IFR_DISPLAY_INT_DEC, IFR_DISPLAY_UINT_HEX, IFR_FLAG_CALLBACK, IFR_NUMERIC_SIZE,
+    IFR_NUMERIC_SIZE_1, IFR_NUMERIC_SIZE_2, IFR_NUMERIC_SIZE_4, IFR_OPTION_DEFAULT,
+    IFR_OPTION_DEFAULT_MFG, IFR_WARNING_IF_OP, IfrDate, IfrNumericData, IfrPassword,
+    IfrResetButton, IfrString, IfrText, IfrTime, IfrWarningIf, QF_DATE_DAY_SUPPRESS,
+    QF_DATE_MONTH_SUPPRESS, QF_DATE_YEAR_SUPPRESS, QF_TIME_HOUR_SUPPRESS, QF_TIME_MINUTE_SUPPRESS,
+    QF_TIME_SECOND_SUPPRESS, scope_opcodes,
+};
+use crate::key::{Key, Modifiers, key_event, raw_key};
+use crate::pointer::{Pointer, PointerEvent};
 use crate::ui::Ui;
 
 // TODO: Move to uefi library {
@@ -213,6 +230,17 @@ pub struct StatementErrorInfo {
     pub TimeOut: u8,
 }
 
+impl StatementErrorInfo {
+    /// Seconds to show the error for, or None to wait for the user
+    pub fn timeout_opt(&self) -> Option<u32> {
+        if self.TimeOut == 0 {
+            None
+        } else {
+            Some(self.TimeOut as u32)
+        }
+    }
+}
+
 pub type ValidateQuestion = extern "efiapi" fn(
     Form: &Form,
     Statement: &Statement,
@@ -223,6 +251,9 @@ pub type ValidateQuestion = extern "efiapi" fn(
 pub type PasswordCheck =
     extern "efiapi" fn(Form: &Form, Statement: &Statement, PasswordString: *const u16) -> Status;
 
+// TODO: Move to uefi library
+const STATUS_NOT_AVAILABLE_YET: Status = Status((1 << (usize::BITS - 1)) | 28);
+
 #[repr(C)]
 pub struct Statement {
     pub Signature: usize,
@@ -294,6 +325,39 @@ pub struct Form {
 
 const FRONT_PAGE_FORM_ID: u16 = 0x7600;
 
+/// Variable store and boot order question of the edk2 boot maintenance manager
+// SAFETY: both ids are transparent wrappers of u16 without public constructors
+const BOOT_MAINT_VARSTORE_ID: VarStoreId = unsafe { mem::transmute(0x1000_u16) };
+const BOOT_OPTION_ORDER_QUESTION_ID: QuestionId = unsafe { mem::transmute(0x1A00_u16) };
+
+/// Milliseconds without typing after which type-ahead in a list of choices starts again
+const TYPE_AHEAD_TIMEOUT_MS: u64 = 1000;
+
+const HII_DISPLAY_GRAYOUT: u32 = 1 << 0;
+const HII_DISPLAY_LOCK: u32 = 1 << 1;
+const HII_DISPLAY_READONLY: u32 = 1 << 2;
+
+/// Skip grayed out, locked, and read-only statements when moving the highlight. Building with
+/// the `highlight_disabled` feature allows highlighting them to read their help, but they still
+/// cannot be changed.
+const SKIP_DISABLED_STATEMENTS: bool = !cfg!(feature = "highlight_disabled");
+
+const BROWSER_SUCCESS: u32 = 0;
+const BROWSER_ERROR: u32 = 1 << 31;
+const BROWSER_SUBMIT_FAIL: u32 = BROWSER_ERROR | 0x01;
+const BROWSER_NO_SUBMIT_IF: u32 = BROWSER_ERROR | 0x02;
+const BROWSER_FORM_NOT_FOUND: u32 = BROWSER_ERROR | 0x03;
+const BROWSER_FORM_SUPPRESS: u32 = BROWSER_ERROR | 0x04;
+const BROWSER_PROTOCOL_NOT_FOUND: u32 = BROWSER_ERROR | 0x05;
+const BROWSER_INCONSISTENT_IF: u32 = BROWSER_ERROR | 0x06;
+const BROWSER_WARNING_IF: u32 = BROWSER_ERROR | 0x07;
+const BROWSER_SUBMIT_FAIL_NO_SUBMIT_IF: u32 = BROWSER_ERROR | 0x08;
+const BROWSER_RECONNECT_REQUIRED: u32 = BROWSER_ERROR | 0x09;
+const BROWSER_RECONNECT_FAIL: u32 = BROWSER_ERROR | 0x0A;
+
+const BROWSER_ACTION_DISCARD: u32 = 1 << 0;
+const BROWSER_ACTION_DEFAULT: u32 = 1 << 1;
+const BROWSER_ACTION_SUBMIT: u32 = 1 << 2;
 const BROWSER_ACTION_NONE: u32 = 1 << 16;
 const BROWSER_ACTION_FORM_EXIT: u32 = 1 << 17;
 
@@ -315,283 +379,1967 @@ pub struct Fde {
 
 static mut DISPLAY: *mut Display = ptr::null_mut();
 
+fn display() -> Result<&'static mut Display> {
+    unsafe {
+        if DISPLAY.is_null() {
+            let display = Display::new(Output::one()?);
+            DISPLAY = Box::into_raw(Box::new(display));
+        }
+        Ok(&mut *DISPLAY)
+    }
+}
+
+static mut POINTER: *mut Pointer = ptr::null_mut();
+
+fn pointer(display: &Display) -> &'static mut Pointer {
+    unsafe {
+        if POINTER.is_null() {
+            let pointer = Pointer::new(display.width(), display.height());
+            POINTER = Box::into_raw(Box::new(pointer));
+        }
+        &mut *POINTER
+    }
+}
+
 #[allow(dead_code)]
 struct ElementOption<'a> {
     option_ptr: *const QuestionOption,
+    name: String,
     prompt: Text<'a>,
     value: IfrTypeValueEnum,
+    flags: u8,
+    enabled: bool,
+    boot_opt: Option<ElementBoot>,
 }
 
-struct Element<'a> {
-    statement_ptr: *const Statement,
-    prompt: String,
-    help: String,
-    value: IfrTypeValueEnum,
-    options: Vec<ElementOption<'a>>,
-    selectable: bool,
-    editable: bool,
-    list: bool,
-    list_i: usize,
-    buffer_opt: Option<&'static mut [u8]>,
-}
-
-#[derive(PartialEq)]
-enum EventType {
-    Driver,
-    Keyboard,
-}
+impl ElementOption<'_> {
+    /// Option number of a boot order option, which stores the number plus one
+    fn boot_number(&self) -> Option<u16> {
+        match self.value {
+            IfrTypeValueEnum::U32(value) => u16::try_from(value.checked_sub(1)?).ok(),
+            _ => None,
+        }
+    }
 
-fn wait_for_events(form: &Form) -> Result<EventType> {
-    let uefi = std::system_table();
-    let mut index = 0;
-    let mut events = vec![uefi.ConsoleIn.WaitForKey];
+    /// Check if the option is disabled, or refers to an inactive boot option
+    fn dimmed(&self) -> bool {
+        !self.enabled || self.boot_opt.as_ref().is_some_and(|boot| !boot.active)
+    }
 
-    if form.FormRefreshEvent != Event(0) {
-        events.push(form.FormRefreshEvent);
+    fn is_zero(&self) -> bool {
+        matches!(
+            self.value,
+            IfrTypeValueEnum::U8(0)
+                | IfrTypeValueEnum::U16(0)
+                | IfrTypeValueEnum::U32(0)
+                | IfrTypeValueEnum::U64(0)
+        )
     }
 
-    Result::from((uefi.BootServices.WaitForEvent)(
-        events.len(),
-        events.as_mut_ptr(),
-        &mut index,
-    ))?;
+    /// Describe the state of this option, if there is anything to show
+    fn label(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(ref boot) = self.boot_opt {
+            parts.extend(boot.kind.name());
+            if !boot.present {
+                parts.push("Not Present");
+            }
+            if !boot.active {
+                parts.push("Inactive");
+            }
+        }
+        if !self.enabled {
+            parts.push("Disabled");
+        }
+        if self.flags & IFR_OPTION_DEFAULT != 0 {
+            parts.push("Default");
+        }
+        if self.flags & IFR_OPTION_DEFAULT_MFG != 0 {
+            parts.push("Manufacturing Default");
+        }
 
-    if index == 0 {
-        Ok(EventType::Keyboard)
-    } else {
-        Ok(EventType::Driver)
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
     }
 }
 
-#[allow(unused_assignments)]
-fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
-    let hii_string = <&'static mut HiiStringProtocol>::one()?;
+/// Details of the `Boot####` variable an ordered list option refers to
+struct ElementBoot {
+    device_path_opt: Option<String>,
+    kind: DeviceKind,
+    present: bool,
+    active: bool,
+}
 
-    let string =
-        |string_id: StringId| -> Result<String> { hii_string.string(form.HiiHandle, string_id) };
+/// Text of boot option device paths, kept until the browser exits
+static mut BOOT_PATH_TEXTS: *mut Vec<(Vec<u8>, Option<String>)> = ptr::null_mut();
 
-    let display: &mut Display = unsafe {
-        if DISPLAY.is_null() {
-            let display = Display::new(Output::one()?);
-            DISPLAY = Box::into_raw(Box::new(display));
+/// Resolve the `Boot####` variables of the boot maintenance manager's boot order list, which
+/// stores option numbers plus one so zero can end the list. Devices and attributes can change
+/// while the browser runs, so only the text of device paths is cached.
+fn boot_details(options: &[ElementOption]) -> Vec<Option<ElementBoot>> {
+    let texts = unsafe {
+        if BOOT_PATH_TEXTS.is_null() {
+            BOOT_PATH_TEXTS = Box::into_raw(Box::new(Vec::new()));
         }
-        &mut *DISPLAY
+        &mut *BOOT_PATH_TEXTS
     };
 
-    let (display_w, display_h) = (display.width(), display.height());
-
-    let scale = if display_h > 1440 {
-        4
-    } else if display_h > 720 {
-        2
-    } else {
-        1
-    };
+    let device_paths = DevicePathProtocol::all();
+    let mut to_text_opt = None;
+    let mut boots = Vec::new();
+    for option in options.iter() {
+        let Some(load_option) = option
+            .boot_number()
+            .and_then(|number| LoadOption::read(number).ok())
+        else {
+            boots.push(None);
+            continue;
+        };
 
-    // Style {
-    let margin_lr = 8 * scale;
-    let margin_tb = 4 * scale;
+        let device_path_opt = match texts
+            .iter()
+            .find(|(device_path, _)| *device_path == load_option.device_path)
+        {
+            Some((_, text_opt)) => text_opt.clone(),
+            None => {
+                let to_text = to_text_opt.get_or_insert_with(|| DevicePathToText::one().ok());
+                let text_opt = to_text
+                    .as_ref()
+                    .and_then(|to_text| load_option.device_path_text(to_text));
+                texts.push((load_option.device_path.clone(), text_opt.clone()));
+                text_opt
+            }
+        };
 
-    let title_font_size = (20 * scale) as f32;
-    let font_size = (16 * scale) as f32; // (display_h as f32) / 26.0
-    let help_font_size = (12 * scale) as f32;
-    // } Style
+        boots.push(Some(ElementBoot {
+            device_path_opt,
+            kind: load_option.device_kind(),
+            present: load_option.device_present(&device_paths),
+            active: load_option.active(),
+        }));
+    }
+    boots
+}
 
-    let ui = Ui::new()?;
+struct ElementString {
+    value: String,
+    cursor: usize,
+    min: usize,
+    max: usize,
+    masked: bool,
+    // First character shown while editing, kept so text only scrolls when the cursor leaves it
+    scroll: Cell<usize>,
+}
 
-    'render: loop {
-        let mut hotkey_helps = Vec::new();
-        for hotkey in form.HotKeyListHead.iter() {
-            let hotkey_help = ffi::nstr(hotkey.HelpString);
-            hotkey_helps.push(hotkey_help);
+impl ElementString {
+    fn new(value: String, min: usize, max: usize) -> Self {
+        let cursor = value.chars().count();
+        Self {
+            value,
+            cursor,
+            min,
+            max,
+            masked: false,
+            scroll: Cell::new(0),
         }
+    }
 
-        let mut selected = !0;
-        let mut editing = false;
-        let mut elements = Vec::new();
-        for statement in form.StatementListHead.iter() {
-            let statement_ptr = statement as *const _;
+    /// Masked entry of a secret, with space reserved for the longest value so it is never
+    /// copied to a larger allocation, leaving the old one behind
+    fn secret(min: usize, max: usize) -> Self {
+        // Characters that can be stored as UCS-2 are at most three bytes of UTF-8
+        let mut string = Self::new(String::with_capacity(max * 3), min, max);
+        string.masked = true;
+        string
+    }
 
-            let mut options = Vec::new();
-            for option in statement.OptionListHead.iter() {
-                let option_ptr = option as *const _;
-                if let Some(op) = option.OptionOpCode() {
-                    let value = unsafe { op.Value.to_enum(op.Kind) };
-                    let prompt = ui
-                        .font
-                        .render(&string(op.Option).unwrap_or_default(), font_size);
-                    options.push(ElementOption {
-                        option_ptr,
-                        prompt,
-                        value,
-                    });
-                }
-            }
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
 
-            let add_element =
-                |header: IfrStatementHeader, selectable: bool, editable: bool, list: bool| {
-                    let value = unsafe {
-                        statement
-                            .CurrentValue
-                            .Value
-                            .to_enum(statement.CurrentValue.Kind)
-                    };
-                    let buffer_opt = if statement.CurrentValue.Buffer.is_null() {
-                        None
-                    } else {
-                        let buffer = unsafe {
-                            slice::from_raw_parts_mut(
-                                statement.CurrentValue.Buffer,
-                                statement.CurrentValue.BufferLen as usize,
-                            )
-                        };
-                        // Order list according to buffer
-                        if list {
-                            let mut offset = 0;
-                            for i in 0..options.len() {
-                                for j in i..options.len() {
-                                    macro_rules! check_option {
-                                        ($x:ident) => {{
-                                            let next_offset = offset + mem::size_of_val(&$x);
-                                            if next_offset <= buffer.len() {
-                                                let mut x_copy = $x;
-                                                unsafe {
-                                                    ptr::copy(
-                                                        buffer.as_ptr().add(offset) as *const _,
-                                                        &mut x_copy,
-                                                        1,
-                                                    );
-                                                };
-                                                if $x == x_copy {
-                                                    offset = next_offset;
-                                                    true
-                                                } else {
-                                                    false
-                                                }
-                                            } else {
-                                                false
-                                            }
-                                        }};
-                                    }
-                                    let matches = match options[j].value {
-                                        IfrTypeValueEnum::U8(u8) => check_option!(u8),
-                                        IfrTypeValueEnum::U16(u16) => check_option!(u16),
-                                        IfrTypeValueEnum::U32(u32) => check_option!(u32),
-                                        IfrTypeValueEnum::U64(u64) => check_option!(u64),
-                                        _ => false,
-                                    };
-                                    if matches {
-                                        if i != j {
-                                            options.swap(i, j);
-                                        }
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        Some(buffer)
-                    };
-                    if statement_ptr == form.HighLightedStatement || (selected == !0 && selectable)
-                    {
-                        selected = elements.len();
-                    }
-                    elements.push(Element {
-                        statement_ptr,
-                        prompt: string(header.Prompt).unwrap_or_default(),
-                        help: string(header.Help).unwrap_or_default(),
-                        value,
-                        options,
-                        selectable,
-                        editable,
-                        list,
-                        list_i: 0,
-                        buffer_opt,
-                    });
-                };
+    /// Clear the value, overwriting the old contents in memory
+    fn clear(&mut self) {
+        wipe_string(&mut self.value);
+        self.cursor = 0;
+    }
 
-            if let Some(op) = statement.OpCode() {
-                macro_rules! cast {
-                    ($type:ty) => {{ op.cast::<$type>() }};
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.value.len(), |(i, _)| i)
+    }
+
+    /// Handle a key while editing, returning true if the key was consumed
+    fn input(&mut self, key: &Key) -> bool {
+        match *key {
+            Key::Left => {
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            Key::Right => {
+                self.cursor = cmp::min(self.cursor + 1, self.len());
+            }
+            Key::Home => {
+                self.cursor = 0;
+            }
+            Key::End => {
+                self.cursor = self.len();
+            }
+            Key::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    let i = self.byte_index(self.cursor);
+                    self.value.remove(i);
                 }
-                match op.OpCode {
-                    IfrOpCode::Action => {
-                        if let Some(action) = unsafe { cast!(IfrAction) } {
-                            add_element(action.QuestionHeader.Header, true, false, false);
-                        }
-                    }
-                    IfrOpCode::Checkbox => {
-                        if let Some(checkbox) = unsafe { cast!(IfrCheckbox) } {
-                            add_element(checkbox.Question.Header, true, true, false);
-                        }
-                    }
-                    IfrOpCode::Numeric => {
-                        if let Some(numeric) = unsafe { cast!(IfrNumeric) } {
-                            add_element(numeric.Question.Header, true, true, false);
-                        }
-                    }
-                    IfrOpCode::OneOf => {
-                        if let Some(one_of) = unsafe { cast!(IfrOneOf) } {
-                            add_element(one_of.Question.Header, true, true, false);
-                        }
-                    }
-                    IfrOpCode::OrderedList => {
-                        if let Some(ordered_list) = unsafe { cast!(IfrOrderedList) } {
-                            add_element(ordered_list.Question.Header, true, true, true);
-                        }
-                    }
-                    IfrOpCode::Ref => {
-                        if let Some(ref_) = unsafe { cast!(IfrRef) } {
-                            add_element(ref_.Question.Header, true, false, false);
-                        }
-                    }
-                    IfrOpCode::Subtitle => {
-                        if let Some(subtitle) = unsafe { cast!(IfrSubtitle) } {
-                            add_element(subtitle.Statement, false, false, false);
-                        }
-                    }
-                    _ => (),
+            }
+            Key::Delete => {
+                if self.cursor < self.len() {
+                    let i = self.byte_index(self.cursor);
+                    self.value.remove(i);
+                }
+            }
+            // Only characters that can be stored as UCS-2 are accepted
+            Key::Character(c) if !c.is_control() && (c as u32) <= 0xFFFF => {
+                if self.len() < self.max {
+                    let i = self.byte_index(self.cursor);
+                    self.value.insert(i, c);
+                    self.cursor += 1;
                 }
             }
+            _ => return false,
         }
+        true
+    }
+}
 
-        let title_opt = string(form.FormTitle).ok();
-        let mut element_start = 0;
-        'display: loop {
-            display.set(ui.background_color);
-
-            let draw_value_box = |display: &mut Display,
+impl Drop for ElementString {
+    fn drop(&mut self) {
+        if self.masked {
+            wipe_string(&mut self.value);
+        }
+    }
+}
+
+/// Overwrite memory with zeroes in a way that is not optimized out
+fn wipe(ptr: *mut u8, len: usize) {
+    for i in 0..len {
+        unsafe { ptr::write_volatile(ptr.add(i), 0) };
+    }
+}
+
+/// Clear a string, overwriting all of its allocation, which still holds any removed characters
+fn wipe_string(string: &mut String) {
+    let bytes = unsafe { string.as_mut_vec() };
+    wipe(bytes.as_mut_ptr(), bytes.capacity());
+    bytes.clear();
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NumericDisplay {
+    Signed,
+    Unsigned,
+    Hex,
+}
+
+struct ElementNumeric {
+    size: u32,
+    display: NumericDisplay,
+    min: i128,
+    max: i128,
+    step: i128,
+    input_opt: Option<String>,
+}
+
+impl ElementNumeric {
+    fn new(op: &IfrOpHeader) -> Option<Self> {
+        let flags = unsafe { op.cast::<IfrNumericData<u8>>() }?.Flags;
+
+        let display = match flags & IFR_DISPLAY {
+            IFR_DISPLAY_INT_DEC => NumericDisplay::Signed,
+            IFR_DISPLAY_UINT_HEX => NumericDisplay::Hex,
+            _ => NumericDisplay::Unsigned,
+        };
+        let signed = display == NumericDisplay::Signed;
+
+        macro_rules! range {
+            ($u:ty, $i:ty) => {{
+                let data = unsafe { op.cast::<IfrNumericData<$u>>() }?;
+                let (min, max, step) = (data.MinValue, data.MaxValue, data.Step);
+                if signed {
+                    (min as $i as i128, max as $i as i128, step as i128)
+                } else {
+                    (min as i128, max as i128, step as i128)
+                }
+            }};
+        }
+
+        let (size, (min, max, step)) = match flags & IFR_NUMERIC_SIZE {
+            IFR_NUMERIC_SIZE_1 => (1, range!(u8, i8)),
+            IFR_NUMERIC_SIZE_2 => (2, range!(u16, i16)),
+            IFR_NUMERIC_SIZE_4 => (4, range!(u32, i32)),
+            _ => (8, range!(u64, i64)),
+        };
+
+        Some(Self {
+            size,
+            display,
+            min,
+            max,
+            step,
+            input_opt: None,
+        })
+    }
+
+    /// Interpret a question value according to the size and display flags
+    fn get(&self, value: &IfrTypeValueEnum) -> i128 {
+        let raw = match *value {
+            IfrTypeValueEnum::U8(x) => x as u64,
+            IfrTypeValueEnum::U16(x) => x as u64,
+            IfrTypeValueEnum::U32(x) => x as u64,
+            IfrTypeValueEnum::U64(x) => x,
+            _ => 0,
+        };
+        if self.display == NumericDisplay::Signed {
+            // Sign extend from the size of the question
+            let shift = 128 - self.size * 8;
+            ((raw as i128) << shift) >> shift
+        } else {
+            raw as i128
+        }
+    }
+
+    fn to_value(&self, n: i128) -> IfrTypeValueEnum {
+        match self.size {
+            1 => IfrTypeValueEnum::U8(n as u8),
+            2 => IfrTypeValueEnum::U16(n as u16),
+            4 => IfrTypeValueEnum::U32(n as u32),
+            _ => IfrTypeValueEnum::U64(n as u64),
+        }
+    }
+
+    fn format(&self, n: i128) -> String {
+        match self.display {
+            NumericDisplay::Hex => format!("0x{n:X}"),
+            _ => format!("{n}"),
+        }
+    }
+
+    /// Start of typed input, which is kept with the digits so it can be shown as is
+    fn prefix(&self) -> &'static str {
+        match self.display {
+            NumericDisplay::Hex => "0x",
+            _ => "",
+        }
+    }
+
+    /// Step up or down, wrapping around at the ends of the range
+    fn adjust(&self, n: i128, increment: bool) -> i128 {
+        let step = cmp::max(self.step, 1);
+        if increment {
+            if n + step <= self.max {
+                n + step
+            } else {
+                self.min
+            }
+        } else if n - step >= self.min {
+            n - step
+        } else {
+            self.max
+        }
+    }
+
+    /// Parse typed input, returning None if it is not an allowed value
+    fn parse(&self) -> Option<i128> {
+        let digits = &self.input_opt.as_ref()?[self.prefix().len()..];
+        let n = match self.display {
+            NumericDisplay::Hex => i128::from_str_radix(digits, 16).ok()?,
+            _ => digits.parse::<i128>().ok()?,
+        };
+        if n < self.min || n > self.max {
+            return None;
+        }
+        if self.step > 0 && (n - self.min) % self.step != 0 {
+            return None;
+        }
+        Some(n)
+    }
+
+    /// Handle a key while editing, returning true if the key was consumed
+    fn input(&mut self, key: &Key, value: &mut IfrTypeValueEnum) -> bool {
+        let signed = self.display == NumericDisplay::Signed;
+        let prefix = self.prefix();
+        match *key {
+            Key::Up | Key::Character('+') => {
+                self.input_opt = None;
+                *value = self.to_value(self.adjust(self.get(value), true));
+            }
+            Key::Down => {
+                self.input_opt = None;
+                *value = self.to_value(self.adjust(self.get(value), false));
+            }
+            // Signed values use minus to start a negative number instead
+            Key::Character('-') if !signed => {
+                self.input_opt = None;
+                *value = self.to_value(self.adjust(self.get(value), false));
+            }
+            Key::Backspace => {
+                if let Some(ref mut input) = self.input_opt {
+                    input.pop();
+                    if input.len() <= prefix.len() {
+                        self.input_opt = None;
+                    }
+                }
+            }
+            Key::Character(c) => {
+                let input = self.input_opt.get_or_insert_with(|| prefix.to_string());
+                let digits = input.len() - prefix.len();
+                let allowed = match self.display {
+                    NumericDisplay::Hex => c.is_ascii_hexdigit() && digits < self.size as usize * 2,
+                    _ => (c.is_ascii_digit() || (signed && c == '-' && digits == 0)) && digits < 20,
+                };
+                if allowed {
+                    input.push(c.to_ascii_uppercase());
+                } else if digits == 0 {
+                    self.input_opt = None;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DateTimeKind {
+    Date,
+    Time,
+}
+
+struct ElementDateTime {
+    kind: DateTimeKind,
+    /// Month, day and year for dates, hour, minute and second for times
+    fields: [u16; 3],
+    suppressed: [bool; 3],
+    field: usize,
+    input: String,
+}
+
+impl ElementDateTime {
+    fn new(kind: DateTimeKind, flags: u8, value: &IfrTypeValueEnum) -> Self {
+        let (fields, suppress_flags) = match kind {
+            DateTimeKind::Date => {
+                let fields = match *value {
+                    IfrTypeValueEnum::Date(date) => [date.Month as u16, date.Day as u16, date.Year],
+                    _ => [1, 1, 2000],
+                };
+                (
+                    fields,
+                    [
+                        QF_DATE_MONTH_SUPPRESS,
+                        QF_DATE_DAY_SUPPRESS,
+                        QF_DATE_YEAR_SUPPRESS,
+                    ],
+                )
+            }
+            DateTimeKind::Time => {
+                let fields = match *value {
+                    IfrTypeValueEnum::Time(time) => {
+                        [time.Hour as u16, time.Minute as u16, time.Second as u16]
+                    }
+                    _ => [0, 0, 0],
+                };
+                (
+                    fields,
+                    [
+                        QF_TIME_HOUR_SUPPRESS,
+                        QF_TIME_MINUTE_SUPPRESS,
+                        QF_TIME_SECOND_SUPPRESS,
+                    ],
+                )
+            }
+        };
+
+        let suppressed = suppress_flags.map(|flag| flags & flag != 0);
+        let field = suppressed.iter().position(|x| !x).unwrap_or(0);
+        Self {
+            kind,
+            fields,
+            suppressed,
+            field,
+            input: String::new(),
+        }
+    }
+
+    fn range(&self, i: usize) -> (u16, u16) {
+        match (self.kind, i) {
+            (DateTimeKind::Date, 0) => (1, 12),
+            (DateTimeKind::Date, 1) => (1, days_in_month(self.fields[2], self.fields[0])),
+            (DateTimeKind::Date, _) => (1, 9999),
+            (DateTimeKind::Time, 0) => (0, 23),
+            (DateTimeKind::Time, _) => (0, 59),
+        }
+    }
+
+    fn width(&self, i: usize) -> usize {
+        if self.kind == DateTimeKind::Date && i == 2 {
+            4
+        } else {
+            2
+        }
+    }
+
+    fn separator(&self) -> &'static str {
+        match self.kind {
+            DateTimeKind::Date => "/",
+            DateTimeKind::Time => ":",
+        }
+    }
+
+    fn field_string(&self, i: usize) -> String {
+        if i == self.field && !self.input.is_empty() {
+            self.input.clone()
+        } else {
+            format!("{:01$}", self.fields[i], self.width(i))
+        }
+    }
+
+    fn to_value(&self) -> IfrTypeValueEnum {
+        match self.kind {
+            DateTimeKind::Date => IfrTypeValueEnum::Date(HiiDate {
+                Year: self.fields[2],
+                Month: self.fields[0] as u8,
+                Day: self.fields[1] as u8,
+            }),
+            DateTimeKind::Time => IfrTypeValueEnum::Time(HiiTime {
+                Hour: self.fields[0] as u8,
+                Minute: self.fields[1] as u8,
+                Second: self.fields[2] as u8,
+            }),
+        }
+    }
+
+    /// Keep the day valid after the month or year changed
+    fn clamp(&mut self) {
+        if self.kind == DateTimeKind::Date {
+            let (_, max) = self.range(1);
+            self.fields[1] = cmp::min(self.fields[1], max);
+        }
+    }
+
+    /// Store typed input in the current field, returning false if it is out of range
+    fn apply_input(&mut self) -> bool {
+        if self.input.is_empty() {
+            return true;
+        }
+        let (min, max) = self.range(self.field);
+        let valid = match self.input.parse::<u16>() {
+            Ok(n) if n >= min && n <= max => {
+                self.fields[self.field] = n;
+                self.clamp();
+                true
+            }
+            _ => false,
+        };
+        self.input.clear();
+        valid
+    }
+
+    fn move_field(&mut self, forward: bool) {
+        self.apply_input();
+        let mut i = self.field;
+        for _ in 0..self.fields.len() {
+            i = if forward {
+                (i + 1) % self.fields.len()
+            } else {
+                (i + self.fields.len() - 1) % self.fields.len()
+            };
+            if !self.suppressed[i] {
+                self.field = i;
+                break;
+            }
+        }
+    }
+
+    /// Step the current field up or down, wrapping around at the ends of its range
+    fn adjust(&mut self, increment: bool) {
+        self.input.clear();
+        let (min, max) = self.range(self.field);
+        let n = self.fields[self.field];
+        self.fields[self.field] = if increment {
+            if n < max { n + 1 } else { min }
+        } else if n > min {
+            n - 1
+        } else {
+            max
+        };
+        self.clamp();
+    }
+
+    /// Handle a key while editing, returning true if the key was consumed
+    fn input(&mut self, key: &Key, value: &mut IfrTypeValueEnum) -> bool {
+        match *key {
+            Key::Left => self.move_field(false),
+            Key::Right | Key::Tab => self.move_field(true),
+            Key::Up | Key::Character('+') => self.adjust(true),
+            Key::Down | Key::Character('-') => self.adjust(false),
+            Key::Backspace => {
+                self.input.pop();
+            }
+            Key::Character(c) if c.is_ascii_digit() => {
+                self.input.push(c);
+                if self.input.len() >= self.width(self.field) {
+                    // Field is complete, continue with the next one
+                    self.move_field(true);
+                }
+            }
+            _ => return false,
+        }
+        *value = self.to_value();
+        true
+    }
+}
+
+fn days_in_month(year: u16, month: u16) -> u16 {
+    match month {
+        2 => {
+            if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 {
+                29
+            } else {
+                28
+            }
+        }
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PasswordStage {
+    Old,
+    New,
+    Confirm,
+}
+
+impl PasswordStage {
+    fn prompt(&self) -> &'static str {
+        match self {
+            PasswordStage::Old => "Please type in your password",
+            PasswordStage::New => "Please type in your new password",
+            PasswordStage::Confirm => "Please confirm your new password",
+        }
+    }
+}
+
+struct ElementPassword {
+    stage: PasswordStage,
+    new: String,
+    callback: bool,
+}
+
+impl Drop for ElementPassword {
+    fn drop(&mut self) {
+        wipe_string(&mut self.new);
+    }
+}
+
+/// Start of a choice name typed while choosing from a one-of popup
+#[derive(Default)]
+struct TypeAhead {
+    prefix: String,
+    // Time of day in milliseconds when the last character was typed
+    time_opt: Option<u64>,
+}
+
+impl TypeAhead {
+    fn clear(&mut self) {
+        self.prefix.clear();
+        self.time_opt = None;
+    }
+
+    /// Add a typed character, starting again after a pause
+    fn push(&mut self, c: char) {
+        // Some clocks only count seconds, so the pause must be longer than a second
+        let now_opt = time_of_day_ms();
+        let idle = match (now_opt, self.time_opt) {
+            (Some(now), Some(last)) => now
+                .checked_sub(last)
+                .is_none_or(|idle| idle > TYPE_AHEAD_TIMEOUT_MS),
+            _ => false,
+        };
+        self.time_opt = now_opt;
+        if idle {
+            self.prefix.clear();
+        }
+        self.prefix.push(c);
+    }
+}
+
+/// What the form display does after Enter is pressed on an element being edited
+enum EnterAction {
+    /// Keep editing, after showing the message if there is one
+    Edit(Option<String>),
+    /// Stop editing, after showing the message if there is one
+    Exit(Option<String>),
+    /// Pass the value to the browser
+    Submit,
+}
+
+struct Element<'a> {
+    statement_ptr: *const Statement,
+    prompt: String,
+    help: String,
+    value: IfrTypeValueEnum,
+    options: Vec<ElementOption<'a>>,
+    selectable: bool,
+    editable: bool,
+    disabled: bool,
+    locked: bool,
+    changed: bool,
+    depth: usize,
+    list: bool,
+    list_i: usize,
+    list_start: usize,
+    // Options can be disabled by leaving them out of the list, which ends with zeroes
+    list_disable: bool,
+    buffer_opt: Option<&'static mut [u8]>,
+    string_opt: Option<ElementString>,
+    password_opt: Option<ElementPassword>,
+    numeric_opt: Option<ElementNumeric>,
+    date_time_opt: Option<ElementDateTime>,
+    text_two_opt: Option<String>,
+    default_id_opt: Option<u16>,
+    // Height when last laid out, with the highlight and list box options it was laid out for
+    height_cache: Cell<Option<(bool, usize, usize, i32)>>,
+    // Text shown for the value, with the value it was formatted from
+    value_cache: RefCell<Option<(IfrTypeValueEnum, String)>>,
+}
+
+impl Element<'_> {
+    /// Text shown for the value, which is only formatted again after the value changed
+    fn value_string(&self) -> Ref<'_, str> {
+        let formatted =
+            matches!(*self.value_cache.borrow(), Some((value, _)) if value == self.value);
+        if !formatted {
+            let string = match (&self.numeric_opt, self.value) {
+                (Some(numeric), value) => numeric.format(numeric.get(&value)),
+                (None, IfrTypeValueEnum::U8(value)) => format!("{value}"),
+                (None, IfrTypeValueEnum::U16(value)) => format!("{value}"),
+                (None, IfrTypeValueEnum::U32(value)) => format!("{value}"),
+                (None, IfrTypeValueEnum::U64(value)) => format!("{value}"),
+                (None, other) => format!("{other:?}"),
+            };
+            *self.value_cache.borrow_mut() = Some((self.value, string));
+        }
+        Ref::map(self.value_cache.borrow(), |cache| {
+            cache.as_ref().map_or("", |(_, string)| string.as_str())
+        })
+    }
+
+    /// Check if the prompt or help contains `search`, ignoring case
+    fn matches_search(&self, search: &str) -> bool {
+        let search = search.to_lowercase();
+        self.prompt.to_lowercase().contains(&search) || self.help.to_lowercase().contains(&search)
+    }
+
+    /// Options shown in a list box of `rows` options
+    fn list_range(&self, rows: usize) -> ops::Range<usize> {
+        self.list_start..cmp::min(self.list_start + rows, self.options.len())
+    }
+
+    /// Select the first option at or after `list_i` with a name starting with `prefix`, ignoring
+    /// case, returning false if there is none
+    fn select_option_prefix(&mut self, prefix: &str, skip_current: bool) -> bool {
+        let prefix = prefix.to_lowercase();
+        let len = self.options.len();
+        let start = self.list_i + if skip_current { 1 } else { 0 };
+        for offset in 0..len {
+            let i = (start + offset) % len;
+            if self.options[i].name.to_lowercase().starts_with(&prefix) {
+                self.list_i = i;
+                self.value = self.options[i].value;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check if options of the ordered list can be enabled and disabled
+    fn list_toggles(&self) -> bool {
+        self.list_disable || self.options.iter().any(|o| o.boot_opt.is_some())
+    }
+
+    /// Enable or disable the ordered list option at `list_i`. Boot options are activated or
+    /// deactivated in place, other options are moved to the boundary between enabled and
+    /// disabled options. Returns false if the option cannot be disabled.
+    fn toggle_list_option(&mut self) -> bool {
+        let Some(option) = self.options.get_mut(self.list_i) else {
+            return true;
+        };
+        // Boot options stay in the boot order, as the boot maintenance manager would delete
+        // options left out of it
+        if let Some(ref mut boot) = option.boot_opt {
+            boot.active = !boot.active;
+            return true;
+        }
+        if !self.list_disable {
+            return false;
+        }
+        // Disabled options are left out of the value, which is padded with zeroes, so an
+        // option with a value of zero could not be told apart from the padding
+        if option.enabled && option.is_zero() {
+            return false;
+        }
+        let mut option = self.options.remove(self.list_i);
+        option.enabled = !option.enabled;
+        let enabled_count = self.options.iter().filter(|o| o.enabled).count();
+        self.list_i = enabled_count;
+        self.options.insert(self.list_i, option);
+        true
+    }
+
+    /// Move the ordered list option at `list_i` up one place, returning false if it cannot move.
+    /// Enabled and disabled options are not mixed.
+    fn move_list_option_up(&mut self) -> bool {
+        if self.list_i == 0
+            || self.list_i >= self.options.len()
+            || self.options[self.list_i].enabled != self.options[self.list_i - 1].enabled
+        {
+            return false;
+        }
+        self.list_i -= 1;
+        self.options.swap(self.list_i, self.list_i + 1);
+        true
+    }
+
+    /// Move the ordered list option at `list_i` down one place, returning false if it cannot
+    /// move. Enabled and disabled options are not mixed.
+    fn move_list_option_down(&mut self) -> bool {
+        if self.list_i + 1 >= self.options.len()
+            || self.options[self.list_i].enabled != self.options[self.list_i + 1].enabled
+        {
+            return false;
+        }
+        self.options.swap(self.list_i, self.list_i + 1);
+        self.list_i += 1;
+        true
+    }
+
+    /// Scroll the list box so the option at `list_i` is shown
+    fn scroll_list(&mut self, rows: usize) {
+        if self.list_i < self.list_start {
+            self.list_start = self.list_i;
+        } else if self.list_i >= self.list_start + rows {
+            self.list_start = self.list_i + 1 - rows;
+        }
+        self.list_start = cmp::min(self.list_start, self.options.len().saturating_sub(rows));
+    }
+
+    /// Check if a value is drawn beside or below the prompt
+    fn has_value(&self) -> bool {
+        self.list
+            || self.editable
+            || !self.options.is_empty()
+            || self.string_opt.is_some()
+            || self.numeric_opt.is_some()
+            || self.date_time_opt.is_some()
+            || self.text_two_opt.is_some()
+    }
+
+    /// Maximum number of characters the question buffer can hold, not counting the nul terminator
+    fn buffer_chars(&self) -> usize {
+        self.buffer_opt
+            .as_ref()
+            .map_or(0, |buffer| (buffer.len() / 2).saturating_sub(1))
+    }
+
+    /// Start editing, with the one-of popup at the current choice
+    fn start_editing(&mut self) {
+        if !self.list {
+            self.list_i = self
+                .options
+                .iter()
+                .position(|o| o.value == self.value)
+                .unwrap_or(0);
+        }
+    }
+
+    /// Handle a key while editing, returning true if the key was consumed
+    fn input(&mut self, key: &Key) -> bool {
+        if let Some(ref mut string) = self.string_opt {
+            if string.input(key) {
+                return true;
+            }
+        }
+        if let Some(ref mut numeric) = self.numeric_opt {
+            if numeric.input(key, &mut self.value) {
+                return true;
+            }
+        }
+        if let Some(ref mut date_time) = self.date_time_opt {
+            if date_time.input(key, &mut self.value) {
+                return true;
+            }
+        }
+        let len = self.options.len();
+        match *key {
+            // Ordered lists move the selection, one-of questions change the choice, wrapping
+            // around at the ends
+            Key::Down | Key::Up if len > 0 => {
+                let down = matches!(key, Key::Down);
+                let step = |i: usize| {
+                    if down {
+                        (i + 1) % len
+                    } else {
+                        (i + len - 1) % len
+                    }
+                };
+                if self.list {
+                    self.list_i = step(self.list_i);
+                } else if let Some(i) = self.options.iter().position(|o| o.value == self.value) {
+                    let i = step(i);
+                    self.value = self.options[i].value;
+                    self.list_i = i;
+                }
+            }
+            Key::PageDown if self.list => {
+                self.move_list_option_down();
+            }
+            Key::PageUp if self.list => {
+                self.move_list_option_up();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Select a choice by typing the start of its name
+    fn type_ahead(&mut self, type_ahead: &mut TypeAhead, c: char) {
+        type_ahead.push(c);
+        if !self.select_option_prefix(&type_ahead.prefix, false) {
+            // Start a new search, moving past the current choice so repeating a letter cycles
+            // through its choices
+            type_ahead.prefix.clear();
+            type_ahead.prefix.push(c);
+            self.select_option_prefix(&type_ahead.prefix, true);
+        }
+    }
+
+    /// Apply typed input when Enter is pressed while editing
+    fn finish_input(&mut self) -> EnterAction {
+        if let Some(ref string) = self.string_opt {
+            if string.len() < string.min {
+                // Keep editing until the minimum length is reached
+                return EnterAction::Edit(Some(format!("Minimum length is {}", string.min)));
+            }
+        }
+        if let Some(ref mut numeric) = self.numeric_opt {
+            if numeric.input_opt.is_some() {
+                let n_opt = numeric.parse();
+                numeric.input_opt = None;
+                match n_opt {
+                    Some(n) => self.value = numeric.to_value(n),
+                    // Keep editing if the typed value is not allowed
+                    None => return EnterAction::Edit(None),
+                }
+            }
+        }
+        if let Some(ref mut date_time) = self.date_time_opt {
+            if !date_time.apply_input() {
+                return EnterAction::Edit(None);
+            }
+            self.value = date_time.to_value();
+        }
+        EnterAction::Submit
+    }
+
+    /// Take the next step of changing a password when Enter is pressed, which starts editing
+    /// if `editing` is false. A new password is stored in `user_input` when it is submitted.
+    fn password_enter(
+        &mut self,
+        form: &Form,
+        editing: bool,
+        user_input: &mut UserInput,
+    ) -> Result<EnterAction> {
+        let (Some(password), Some(string)) = (self.password_opt.as_mut(), self.string_opt.as_mut())
+        else {
+            return Ok(EnterAction::Exit(None));
+        };
+        let statement = unsafe { &(*self.statement_ptr) };
+        let action = if !editing {
+            // An empty password tests if the old password is required
+            let status = password_check(form, statement, Some(""));
+            if status == Status::UNSUPPORTED {
+                EnterAction::Exit(Some("Password is not supported".to_string()))
+            } else if status == STATUS_NOT_AVAILABLE_YET {
+                EnterAction::Exit(None)
+            } else {
+                password.stage = if status.is_success() {
+                    PasswordStage::New
+                } else {
+                    PasswordStage::Old
+                };
+                EnterAction::Edit(None)
+            }
+        } else {
+            match password.stage {
+                PasswordStage::Old => {
+                    let status = password_check(form, statement, Some(&string.value));
+                    if status.is_success() {
+                        password.stage = PasswordStage::New;
+                        EnterAction::Edit(None)
+                    } else if status == Status::NOT_READY {
+                        EnterAction::Exit(Some("Incorrect password".to_string()))
+                    } else {
+                        EnterAction::Exit(Some(format!("Failed to check password: {status:?}")))
+                    }
+                }
+                PasswordStage::New => {
+                    // Copied into the space reserved for it
+                    wipe_string(&mut password.new);
+                    password.new.push_str(&string.value);
+                    password.stage = PasswordStage::Confirm;
+                    EnterAction::Edit(None)
+                }
+                PasswordStage::Confirm => {
+                    if string.value != password.new {
+                        if password.callback {
+                            // Reset password state machine
+                            let _ = password_check(form, statement, None);
+                        }
+                        EnterAction::Exit(Some("Passwords do not match".to_string()))
+                    } else {
+                        // The browser passes the new password to the driver, and reports any
+                        // failure through the browser status of the next form display
+                        user_input.SelectedStatement = self.statement_ptr;
+                        unsafe {
+                            ptr::copy(&statement.CurrentValue, &mut user_input.InputValue, 1);
+                        }
+                        let buffer = allocate_string_buffer(
+                            &password.new,
+                            user_input.InputValue.BufferLen as usize,
+                        )?;
+                        user_input.InputValue.Buffer = buffer.as_mut_ptr();
+                        wipe_string(&mut password.new);
+                        EnterAction::Submit
+                    }
+                }
+            }
+        };
+        string.clear();
+        Ok(action)
+    }
+
+    /// Forget input when editing is canceled, so no password is left in memory
+    fn cancel_input(&mut self, form: &Form) {
+        if let Some(ref mut password) = self.password_opt {
+            if password.stage != PasswordStage::Old {
+                // Reset password state machine
+                let statement = unsafe { &(*self.statement_ptr) };
+                let _ = password_check(form, statement, None);
+            }
+            wipe_string(&mut password.new);
+        }
+        if let Some(ref mut string) = self.string_opt {
+            if string.masked {
+                string.clear();
+            }
+        }
+    }
+
+    /// Store the edited value in `input_value` for the browser, returning the previous contents
+    /// of the ordered list buffer so the change can be undone
+    fn store_input(&mut self, input_value: &mut HiiValue) -> Result<Option<Vec<u8>>> {
+        let mut original_opt = None;
+        if self.list {
+            let mut offset = 0;
+            if let Some(ref mut buffer) = self.buffer_opt {
+                original_opt = Some(buffer.to_vec());
+                // Disabled options are left out, ending the list with zeroes
+                for option in self.options.iter().filter(|o| o.enabled) {
+                    macro_rules! copy_option {
+                        ($x:ident) => {{
+                            let next_offset = offset + mem::size_of_val(&$x);
+                            if next_offset <= buffer.len() {
+                                unsafe {
+                                    ptr::copy(&$x, buffer.as_mut_ptr().add(offset) as *mut _, 1)
+                                }
+                            }
+                            offset = next_offset;
+                        }};
+                    }
+                    match option.value {
+                        IfrTypeValueEnum::U8(u8) => copy_option!(u8),
+                        IfrTypeValueEnum::U16(u16) => copy_option!(u16),
+                        IfrTypeValueEnum::U32(u32) => copy_option!(u32),
+                        IfrTypeValueEnum::U64(u64) => copy_option!(u64),
+                        _ => (),
+                    }
+                }
+                if offset < buffer.len() {
+                    for i in offset..buffer.len() {
+                        buffer[i] = 0;
+                    }
+                }
+            }
+        } else if let Some(ref string) = self.string_opt {
+            let buffer = allocate_string_buffer(&string.value, input_value.BufferLen as usize)?;
+            input_value.Buffer = buffer.as_mut_ptr();
+        } else {
+            let (kind, value) = unsafe { self.value.to_union() };
+            input_value.Kind = kind;
+            input_value.Value = value;
+        }
+        Ok(original_opt)
+    }
+
+    /// Undo `store_input` after the value was rejected
+    fn undo_input(&mut self, input_value: &HiiValue, original_opt: Option<Vec<u8>>) {
+        if let (Some(buffer), Some(original)) = (self.buffer_opt.as_mut(), original_opt) {
+            buffer.copy_from_slice(&original);
+        }
+        if !self.list && self.string_opt.is_some() {
+            free_pool(input_value.Buffer);
+        }
+    }
+
+    /// Write the active attribute of boot options, which is not part of the question value,
+    /// returning messages for the options that could not be updated
+    fn write_boot_options(&self) -> Vec<String> {
+        let mut messages = Vec::new();
+        for option in self.options.iter() {
+            let (Some(boot), Some(number)) = (option.boot_opt.as_ref(), option.boot_number())
+            else {
+                continue;
+            };
+            let Ok(mut load_option) = LoadOption::read(number) else {
+                continue;
+            };
+            if load_option.active() == boot.active {
+                continue;
+            }
+            load_option.set_active(boot.active);
+            if let Err(err) = load_option.write(number) {
+                messages.push(format!("Failed to update {}: {err:?}", option.name));
+            }
+        }
+        messages
+    }
+}
+
+/// Flatten a statement list, placing nested statements after their parent along with their depth
+fn nested_statements<'a>(
+    list: &'a ListHead<Statement>,
+    depth: usize,
+    statements: &mut Vec<(&'a Statement, usize)>,
+) {
+    for statement in list.iter() {
+        statements.push((statement, depth));
+        nested_statements(&statement.NestStatementList, depth + 1, statements);
+    }
+}
+
+/// Milliseconds since the start of the day, or None if the time cannot be read
+fn time_of_day_ms() -> Option<u64> {
+    let mut time = Time::default();
+    Result::from((std::system_table().RuntimeServices.GetTime)(
+        &mut time,
+        ptr::null_mut(),
+    ))
+    .ok()?;
+    let seconds = (time.Hour as u64 * 60 + time.Minute as u64) * 60 + time.Second as u64;
+    Some(seconds * 1000 + time.Nanosecond as u64 / 1_000_000)
+}
+
+/// Byte range of the first match of `search` in `text`, ignoring case
+fn search_match(text: &str, search: &str) -> Option<(usize, usize)> {
+    if search.is_empty() {
+        return None;
+    }
+    let search: Vec<char> = search.chars().flat_map(char::to_lowercase).collect();
+    for (start, _) in text.char_indices() {
+        let mut chars = text[start..]
+            .char_indices()
+            .flat_map(|(i, c)| c.to_lowercase().map(move |lower| (i + c.len_utf8(), lower)));
+        let mut end = start;
+        let mut matched = true;
+        for &s in search.iter() {
+            match chars.next() {
+                Some((next_end, c)) if c == s => end = start + next_end,
+                _ => {
+                    matched = false;
+                    break;
+                }
+            }
+        }
+        if matched {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+/// Decode a nul terminated UCS-2 string from a question buffer
+fn buffer_string(buffer: &[u8]) -> String {
+    let mut string = String::new();
+    for chunk in buffer.chunks_exact(2) {
+        let w = u16::from_le_bytes([chunk[0], chunk[1]]);
+        if w == 0 {
+            break;
+        }
+        string.push(char::from_u32(w as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+    string
+}
+
+/// Allocate a pool buffer that the browser takes ownership of
+fn allocate_pool(size: usize) -> Result<&'static mut [u8]> {
+    let uefi = std::system_table();
+    let mut ptr = 0;
+    Result::from((uefi.BootServices.AllocatePool)(
+        MemoryType::EfiBootServicesData,
+        size,
+        &mut ptr,
+    ))?;
+    Ok(unsafe { slice::from_raw_parts_mut(ptr as *mut u8, size) })
+}
+
+fn free_pool(buffer: *mut u8) {
+    let uefi = std::system_table();
+    let _ = (uefi.BootServices.FreePool)(buffer as usize);
+}
+
+/// Copy a string into a new pool buffer of `size` bytes as nul padded UCS-2
+fn allocate_string_buffer(string: &str, size: usize) -> Result<&'static mut [u8]> {
+    let buffer = allocate_pool(size)?;
+    buffer.fill(0);
+    for (chunk, w) in buffer.chunks_exact_mut(2).zip(string.encode_utf16()) {
+        chunk.copy_from_slice(&w.to_le_bytes());
+    }
+    Ok(buffer)
+}
+
+/// Run the validation callback of a question, returning the error if the value is rejected
+fn validate_question(
+    form: &Form,
+    statement: &Statement,
+    value: &HiiValue,
+) -> Option<StatementErrorInfo> {
+    let validate = statement.ValidateQuestion?;
+    let mut error_info: StatementErrorInfo = unsafe { mem::zeroed() };
+    if validate(form, statement, value, &mut error_info) == BROWSER_SUCCESS {
+        None
+    } else {
+        Some(error_info)
+    }
+}
+
+/// Run the browser password check, passing no password resets its state machine
+fn password_check(form: &Form, statement: &Statement, password_opt: Option<&str>) -> Status {
+    let Some(check) = statement.PasswordCheck else {
+        return Status::UNSUPPORTED;
+    };
+
+    match password_opt {
+        Some(password) => {
+            // Reserved up front so the password is not left behind by a reallocation
+            let mut data: Vec<u16> = Vec::with_capacity(password.len() + 1);
+            data.extend(password.encode_utf16().chain(iter::once(0)));
+            let status = check(form, statement, data.as_ptr());
+            // Do not leave the password in memory
+            wipe(data.as_mut_ptr() as *mut u8, data.capacity() * 2);
+            status
+        }
+        None => check(form, statement, ptr::null()),
+    }
+}
+
+/// Message for a browser status, preferring the error string provided by the browser
+fn browser_status_message(form: &Form) -> String {
+    if !form.ErrorString.is_null() {
+        let error_string = ffi::nstr(form.ErrorString);
+        if !error_string.trim().is_empty() {
+            return error_string;
+        }
+    }
+
+    match form.BrowserStatus {
+        BROWSER_SUBMIT_FAIL => "Failed to save changes.",
+        BROWSER_NO_SUBMIT_IF | BROWSER_SUBMIT_FAIL_NO_SUBMIT_IF => {
+            "Changes cannot be saved until invalid settings are corrected."
+        }
+        BROWSER_FORM_NOT_FOUND => "Form not found.",
+        BROWSER_FORM_SUPPRESS => "Form is suppressed, nothing to display.",
+        BROWSER_PROTOCOL_NOT_FOUND => "Protocol not found.",
+        BROWSER_INCONSISTENT_IF => "Setting is inconsistent with other settings.",
+        BROWSER_WARNING_IF => "Warning.",
+        BROWSER_RECONNECT_REQUIRED => "Changes require the device to be reconnected.",
+        BROWSER_RECONNECT_FAIL => "Failed to reconnect the device.",
+        _ => "Unknown browser error.",
+    }
+    .to_string()
+}
+
+/// Timeout in seconds of the warning-if of the highlighted statement that matches `message`
+fn warning_timeout_opt(
+    form: &Form,
+    message: &str,
+    string: impl Fn(StringId) -> Result<String>,
+) -> Option<u32> {
+    if form.HighLightedStatement.is_null() {
+        return None;
+    }
+    let statement = unsafe { &*form.HighLightedStatement };
+    if statement.OpCodePtr.is_null() {
+        return None;
+    }
+
+    let warning_ifs: Vec<&IfrWarningIf> =
+        unsafe { scope_opcodes(statement.OpCodePtr, IFR_WARNING_IF_OP) }
+            .into_iter()
+            .map(|ptr| unsafe { &*(ptr as *const IfrWarningIf) })
+            .collect();
+    // Use the first warning-if when none match, as most questions only have one
+    let warning_if = warning_ifs
+        .iter()
+        .find(|warning_if| string(warning_if.Warning).is_ok_and(|warning| warning == message))
+        .or(warning_ifs.first())?;
+    match warning_if.TimeOut {
+        0 => None,
+        timeout => Some(timeout as u32),
+    }
+}
+
+/// Area an element or option was drawn in, to find what the pointer is on
+struct HitBox {
+    index: usize,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+impl HitBox {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+}
+
+#[derive(PartialEq)]
+enum EventType {
+    Driver,
+    Keyboard,
+    Pointer,
+}
+
+fn wait_for_events(form: &Form, pointer: &Pointer) -> Result<EventType> {
+    let uefi = std::system_table();
+    let mut index = 0;
+    let mut events = vec![key_event()];
+    let pointer_events = pointer.events();
+    events.extend_from_slice(&pointer_events);
+
+    if form.FormRefreshEvent != Event(0) {
+        events.push(form.FormRefreshEvent);
+    }
+
+    Result::from((uefi.BootServices.WaitForEvent)(
+        events.len(),
+        events.as_mut_ptr(),
+        &mut index,
+    ))?;
+
+    if index == 0 {
+        Ok(EventType::Keyboard)
+    } else if index <= pointer_events.len() {
+        Ok(EventType::Pointer)
+    } else {
+        Ok(EventType::Driver)
+    }
+}
+
+#[allow(unused_assignments)]
+fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
+    let hii_string = <&'static mut HiiStringProtocol>::one()?;
+
+    let string =
+        |string_id: StringId| -> Result<String> { hii_string.string(form.HiiHandle, string_id) };
+
+    let display = display()?;
+    let pointer = pointer(display);
+
+    let (display_w, display_h) = (display.width(), display.height());
+
+    let scale = if display_h > 1440 {
+        4
+    } else if display_h > 720 {
+        2
+    } else {
+        1
+    };
+
+    // Style {
+    let margin_lr = 8 * scale;
+    let margin_tb = 4 * scale;
+
+    let title_font_size = (20 * scale) as f32;
+    let font_size = (16 * scale) as f32; // (display_h as f32) / 26.0
+    let help_font_size = (12 * scale) as f32;
+
+    let scrollbar_w = 4 * scale as u32;
+    let list_rows_max = 8;
+    let help_rows = 4;
+
+    // Narrow and portrait displays stack prompts over values, which do not fit side by side
+    let stacked_w = font_size as u32 * 52;
+
+    // Wide displays show help in a column beside the form instead of in the footer, if there
+    // is still room for prompts and values side by side
+    let help_pane_w = if display_w * 2 / 3 >= stacked_w {
+        display_w / 3
+    } else {
+        0
+    };
+    let two_pane = help_pane_w > 0;
+    let body_w = display_w - help_pane_w;
+    let stacked = body_w < stacked_w;
+    // } Style
+
+    let mut ui = Ui::new()?;
+
+    if form.BrowserStatus != BROWSER_SUCCESS {
+        // Show the status over the previous form, and wait for it before redrawing
+        let message = browser_status_message(form);
+        let timeout_opt = if form.BrowserStatus == BROWSER_WARNING_IF {
+            warning_timeout_opt(form, &message, string)
+        } else {
+            None
+        };
+        dialog::message(display, &ui, &message, timeout_opt)?;
+    }
+
+    'render: loop {
+        let mut hotkey_helps = Vec::new();
+        for hotkey in form.HotKeyListHead.iter() {
+            let hotkey_help = ffi::nstr(hotkey.HelpString);
+            hotkey_helps.push(hotkey_help);
+        }
+
+        let mut selected = !0;
+        let mut editing = false;
+        let mut dragging = false;
+        let mut type_ahead = TypeAhead::default();
+        let mut search = String::new();
+        let mut elements = Vec::new();
+        let mut statements = Vec::new();
+        nested_statements(&form.StatementListHead, 0, &mut statements);
+        for (statement, depth) in statements {
+            let statement_ptr = statement as *const _;
+
+            let mut options = Vec::new();
+            for option in statement.OptionListHead.iter() {
+                let option_ptr = option as *const _;
+                if let Some(op) = option.OptionOpCode() {
+                    let value = unsafe { op.Value.to_enum(op.Kind) };
+                    let name = string(op.Option).unwrap_or_default();
+                    let prompt = ui.font.render(&name, font_size);
+                    options.push(ElementOption {
+                        option_ptr,
+                        name,
+                        prompt,
+                        value,
+                        flags: op.Flags,
+                        enabled: true,
+                        boot_opt: None,
+                    });
+                }
+            }
+
+            let add_element =
+                |header: IfrStatementHeader, selectable: bool, editable: bool, list: bool| {
+                    let value = unsafe {
+                        statement
+                            .CurrentValue
+                            .Value
+                            .to_enum(statement.CurrentValue.Kind)
+                    };
+                    let mut list_disable = false;
+                    let buffer_opt = if statement.CurrentValue.Buffer.is_null() {
+                        None
+                    } else {
+                        let buffer = unsafe {
+                            slice::from_raw_parts_mut(
+                                statement.CurrentValue.Buffer,
+                                statement.CurrentValue.BufferLen as usize,
+                            )
+                        };
+                        // Order list according to buffer
+                        if list {
+                            // Options matching the buffer are moved to the start
+                            let mut matched = 0;
+                            let mut offset = 0;
+                            for i in 0..options.len() {
+                                for j in i..options.len() {
+                                    macro_rules! check_option {
+                                        ($x:ident) => {{
+                                            let next_offset = offset + mem::size_of_val(&$x);
+                                            if next_offset <= buffer.len() {
+                                                let mut x_copy = $x;
+                                                unsafe {
+                                                    ptr::copy(
+                                                        buffer.as_ptr().add(offset) as *const _,
+                                                        &mut x_copy,
+                                                        1,
+                                                    );
+                                                };
+                                                if $x == x_copy {
+                                                    offset = next_offset;
+                                                    true
+                                                } else {
+                                                    false
+                                                }
+                                            } else {
+                                                false
+                                            }
+                                        }};
+                                    }
+                                    let matches = match options[j].value {
+                                        IfrTypeValueEnum::U8(u8) => check_option!(u8),
+                                        IfrTypeValueEnum::U16(u16) => check_option!(u16),
+                                        IfrTypeValueEnum::U32(u32) => check_option!(u32),
+                                        IfrTypeValueEnum::U64(u64) => check_option!(u64),
+                                        _ => false,
+                                    };
+                                    if matches {
+                                        if i != j {
+                                            options.swap(i, j);
+                                        }
+                                        matched = i + 1;
+                                        break;
+                                    }
+                                }
+                            }
+                            // Only lists that end with zeroes hold a varying number of options,
+                            // so options missing from them are disabled. The zero padding
+                            // matches an option with a value of zero, so it is always enabled,
+                            // see `Element::toggle_list_option`.
+                            list_disable =
+                                offset < buffer.len() && buffer[offset..].iter().all(|&b| b == 0);
+                            if list_disable {
+                                for (i, option) in options.iter_mut().enumerate() {
+                                    option.enabled = i < matched;
+                                }
+                                // Keep disabled options after enabled ones
+                                options.sort_by_key(|option| !option.enabled);
+                            }
+                        }
+                        Some(buffer)
+                    };
+                    let disabled = statement.Attribute
+                        & (HII_DISPLAY_GRAYOUT | HII_DISPLAY_LOCK | HII_DISPLAY_READONLY)
+                        != 0;
+                    let selectable = selectable && !(disabled && SKIP_DISABLED_STATEMENTS);
+                    // A highlighted statement that cannot be selected is passed over like others
+                    if selectable && (statement_ptr == form.HighLightedStatement || selected == !0)
+                    {
+                        selected = elements.len();
+                    }
+                    elements.push(Element {
+                        statement_ptr,
+                        prompt: string(header.Prompt).unwrap_or_default(),
+                        help: string(header.Help).unwrap_or_default(),
+                        value,
+                        options,
+                        selectable,
+                        editable,
+                        disabled,
+                        locked: statement.Attribute & HII_DISPLAY_LOCK != 0,
+                        changed: statement.SettingChangedFlag,
+                        depth,
+                        list,
+                        list_i: 0,
+                        list_start: 0,
+                        list_disable,
+                        buffer_opt,
+                        string_opt: None,
+                        password_opt: None,
+                        numeric_opt: None,
+                        date_time_opt: None,
+                        text_two_opt: None,
+                        default_id_opt: None,
+                        height_cache: Cell::new(None),
+                        value_cache: RefCell::new(None),
+                    });
+                };
+
+            if let Some(op) = statement.OpCode() {
+                macro_rules! cast {
+                    ($type:ty) => {{ op.cast::<$type>() }};
+                }
+                match op.OpCode {
+                    IfrOpCode::Action => {
+                        if let Some(action) = unsafe { cast!(IfrAction) } {
+                            add_element(action.QuestionHeader.Header, true, false, false);
+                        }
+                    }
+                    IfrOpCode::Checkbox => {
+                        if let Some(checkbox) = unsafe { cast!(IfrCheckbox) } {
+                            add_element(checkbox.Question.Header, true, true, false);
+                        }
+                    }
+                    IfrOpCode::Date => {
+                        if let Some(date) = unsafe { cast!(IfrDate) } {
+                            add_element(date.Question.Header, true, true, false);
+                            if let Some(element) = elements.last_mut() {
+                                element.date_time_opt = Some(ElementDateTime::new(
+                                    DateTimeKind::Date,
+                                    date.Flags,
+                                    &element.value,
+                                ));
+                            }
+                        }
+                    }
+                    IfrOpCode::Numeric => {
+                        if let Some(numeric) = unsafe { cast!(IfrNumeric) } {
+                            add_element(numeric.Question.Header, true, true, false);
+                            if let Some(element) = elements.last_mut() {
+                                element.numeric_opt = ElementNumeric::new(op);
+                            }
+                        }
+                    }
+                    IfrOpCode::OneOf => {
+                        if let Some(one_of) = unsafe { cast!(IfrOneOf) } {
+                            add_element(one_of.Question.Header, true, true, false);
+                        }
+                    }
+                    IfrOpCode::OrderedList => {
+                        if let Some(ordered_list) = unsafe { cast!(IfrOrderedList) } {
+                            add_element(ordered_list.Question.Header, true, true, true);
+                            // Copied out of the packed header
+                            let var_store_id = ordered_list.Question.VarStoreId;
+                            let question_id = ordered_list.Question.QuestionId;
+                            let boot_order = var_store_id == BOOT_MAINT_VARSTORE_ID
+                                && question_id == BOOT_OPTION_ORDER_QUESTION_ID;
+                            if let Some(element) = elements.last_mut().filter(|_| boot_order) {
+                                let boots = boot_details(&element.options);
+                                for (option, boot_opt) in element.options.iter_mut().zip(boots) {
+                                    option.boot_opt = boot_opt;
+                                }
+                            }
+                        }
+                    }
+                    IfrOpCode::Ref => {
+                        if let Some(ref_) = unsafe { cast!(IfrRef) } {
+                            add_element(ref_.Question.Header, true, false, false);
+                        }
+                    }
+                    IfrOpCode::Password => {
+                        if let Some(password) = unsafe { cast!(IfrPassword) } {
+                            add_element(password.Question.Header, true, true, false);
+                            if let Some(element) = elements.last_mut() {
+                                let buffer_max = element.buffer_chars();
+                                let max = cmp::min(password.MaxSize as usize, buffer_max);
+                                element.string_opt =
+                                    Some(ElementString::secret(password.MinSize as usize, max));
+                                element.password_opt = Some(ElementPassword {
+                                    stage: PasswordStage::Old,
+                                    new: String::with_capacity(max * 3),
+                                    callback: password.Question.Flags & IFR_FLAG_CALLBACK != 0,
+                                });
+                            }
+                        }
+                    }
+                    IfrOpCode::ResetButton => {
+                        if let Some(reset_button) = unsafe { cast!(IfrResetButton) } {
+                            add_element(reset_button.Statement, true, false, false);
+                            if let Some(element) = elements.last_mut() {
+                                element.default_id_opt = Some(reset_button.DefaultId);
+                            }
+                        }
+                    }
+                    IfrOpCode::String => {
+                        if let Some(string) = unsafe { cast!(IfrString) } {
+                            add_element(string.Question.Header, true, true, false);
+                            if let Some(element) = elements.last_mut() {
+                                let buffer_max = element.buffer_chars();
+                                let value = element
+                                    .buffer_opt
+                                    .as_deref()
+                                    .map(buffer_string)
+                                    .unwrap_or_default();
+                                element.string_opt = Some(ElementString::new(
+                                    value,
+                                    string.MinSize as usize,
+                                    cmp::min(string.MaxSize as usize, buffer_max),
+                                ));
+                            }
+                        }
+                    }
+                    IfrOpCode::Text => {
+                        if let Some(text) = unsafe { cast!(IfrText) } {
+                            add_element(text.Statement, false, false, false);
+                            if let Some(element) = elements.last_mut() {
+                                element.text_two_opt = string(text.TextTwo).ok();
+                            }
+                        }
+                    }
+                    IfrOpCode::Time => {
+                        if let Some(time) = unsafe { cast!(IfrTime) } {
+                            add_element(time.Question.Header, true, true, false);
+                            if let Some(element) = elements.last_mut() {
+                                element.date_time_opt = Some(ElementDateTime::new(
+                                    DateTimeKind::Time,
+                                    time.Flags,
+                                    &element.value,
+                                ));
+                            }
+                        }
+                    }
+                    IfrOpCode::Subtitle => {
+                        if let Some(subtitle) = unsafe { cast!(IfrSubtitle) } {
+                            add_element(subtitle.Statement, false, false, false);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let title_opt = string(form.FormTitle).ok();
+        let mut element_start = 0;
+        let mut help_start = 0;
+        let mut help_text = String::new();
+        'display: loop {
+            #[cfg(feature = "frame_time")]
+            let frame_timer = FrameTimer::start();
+
+            // Options shown in a list box, limited to the space in the body once it is known
+            let list_rows = Cell::new(list_rows_max);
+
+            display.set(ui.background_color);
+
+            let draw_value_box = |display: &mut Display,
                                   x: i32,
                                   y: i32,
-                                  value: &IfrTypeValueEnum,
+                                  element: &Element,
                                   highlighted: bool|
              -> i32 {
-                //TODO: Do not format in drawing loop
-                let value_string = match value {
-                    IfrTypeValueEnum::U8(value) => format!("{value}"),
-                    IfrTypeValueEnum::U16(value) => format!("{value}"),
-                    IfrTypeValueEnum::U32(value) => format!("{value}"),
-                    IfrTypeValueEnum::U64(value) => format!("{value}"),
-                    IfrTypeValueEnum::Bool(value) => {
-                        return ui.draw_check_box(display, x, y, *value);
+                if let IfrTypeValueEnum::Bool(value) = element.value {
+                    return ui.draw_check_box(display, x, y, value);
+                }
+
+                let rendered = ui.render(&element.value_string(), font_size);
+                ui.draw_text_box(display, x, y, rendered, true, highlighted);
+                rendered.height() as i32
+            };
+
+            let draw_string_box = |display: &mut Display,
+                                   x: i32,
+                                   y: i32,
+                                   string: &ElementString,
+                                   max_w: u32,
+                                   highlighted: bool|
+             -> i32 {
+                // Masked characters are shown as stars, which are one byte each
+                let mask;
+                let shown = if string.masked {
+                    mask = "*".repeat(string.len());
+                    &mask
+                } else {
+                    &string.value
+                };
+                let byte_index = |cursor: usize| {
+                    shown
+                        .char_indices()
+                        .nth(cursor)
+                        .map_or(shown.len(), |(i, _)| i)
+                };
+                // Scroll long strings being edited so the cursor stays in view
+                let mut start = 0;
+                if highlighted {
+                    let cursor_w = max_w.saturating_sub(scale as u32);
+                    start = cmp::min(string.scroll.get(), string.cursor);
+                    while start < string.cursor {
+                        let before = &shown[byte_index(start)..byte_index(string.cursor)];
+                        if ui.render(before, font_size).width() <= cursor_w {
+                            break;
+                        }
+                        start += 1;
+                    }
+                }
+                string.scroll.set(start);
+                let scrolled = &shown[byte_index(start)..];
+                let (_line, rendered) = ui.render_clipped(scrolled, font_size, max_w);
+                let w = cmp::min(cmp::max(rendered.width(), font_size as u32 * 4), max_w);
+                let h = font_size as u32;
+                ui.draw_pretty_box(display, x, y, w, h, highlighted);
+                let text_color = if highlighted {
+                    ui.highlight_text_color
+                } else {
+                    ui.text_color
+                };
+                rendered.draw(display, x, y, text_color);
+                if highlighted {
+                    // Draw cursor
+                    let before = &shown[byte_index(start)..byte_index(string.cursor)];
+                    let cursor_x = x + ui.render(before, font_size).width() as i32;
+                    display.rect(cursor_x, y, scale as u32, h, text_color);
+                }
+                h as i32
+            };
+
+            let draw_numeric_box = |display: &mut Display,
+                                    x: i32,
+                                    y: i32,
+                                    element: &Element,
+                                    numeric: &ElementNumeric,
+                                    max_w: u32,
+                                    highlighted: bool|
+             -> i32 {
+                let (_line, rendered) = match numeric.input_opt {
+                    Some(ref input) => ui.render_clipped(input, font_size, max_w),
+                    None => ui.render_clipped(&element.value_string(), font_size, max_w),
+                };
+                ui.draw_text_box(display, x, y, rendered, true, highlighted);
+                rendered.height() as i32
+            };
+
+            let draw_date_time_box = |display: &mut Display,
+                                      mut x: i32,
+                                      y: i32,
+                                      date_time: &ElementDateTime,
+                                      highlighted: bool|
+             -> i32 {
+                let mut texts = Vec::new();
+                for i in 0..date_time.fields.len() {
+                    if date_time.suppressed[i] {
+                        continue;
+                    }
+                    if !texts.is_empty() {
+                        texts.push((None, ui.render(date_time.separator(), font_size)));
+                    }
+                    texts.push((Some(i), ui.render(&date_time.field_string(i), font_size)));
+                }
+
+                let w = texts.iter().map(|(_, text)| text.width()).sum();
+                let h = font_size as u32;
+                ui.draw_pretty_box(display, x, y, w, h, false);
+                for (i_opt, text) in texts.iter() {
+                    // Only the field being edited is highlighted
+                    let field_highlighted = highlighted && *i_opt == Some(date_time.field);
+                    ui.draw_text_box(display, x, y, text, field_highlighted, field_highlighted);
+                    x += text.width() as i32;
+                }
+                h as i32
+            };
+
+            let draw_option_label =
+                |display: &mut Display, x: i32, y: i32, option: &ElementOption| {
+                    if let Some(label) = option.label() {
+                        let rendered = ui.render(&label, help_font_size);
+                        let label_y = y + (font_size - help_font_size) as i32 / 2;
+                        ui.draw_text_box(display, x, label_y, rendered, false, false);
                     }
-                    other => format!("{other:?}"),
                 };
 
-                // TODO: Do not render in drawing loop
-                let rendered = ui.font.render(&value_string, font_size);
-                ui.draw_text_box(display, x, y, &rendered, true, highlighted);
-                rendered.height() as i32
+            // Counts of ordered list options scrolled out of the list box above and below
+            let list_more_texts = |element: &Element| {
+                let range = element.list_range(list_rows.get());
+                let above = range.start;
+                let below = element.options.len() - range.end;
+                (
+                    (above > 0).then(|| ui.render(&format!("↑ {above} more"), help_font_size)),
+                    (below > 0).then(|| ui.render(&format!("↓ {below} more"), help_font_size)),
+                )
+            };
+
+            // Left edge of the value of an element, beside the prompt or indented below it
+            let value_x = |element: &Element| -> i32 {
+                if stacked {
+                    margin_lr * (element.depth as i32 + 4)
+                } else {
+                    body_w as i32 / 2
+                }
+            };
+
+            // Width available to a prompt, keeping clear of the value and lock icon
+            let prompt_w = |element: &Element| -> u32 {
+                let prompt_x = margin_lr * (element.depth as i32 + 1);
+                let end_x = if stacked || !element.has_value() {
+                    body_w as i32 - margin_lr * 2
+                } else {
+                    value_x(element) - margin_lr * 3
+                };
+                cmp::max(end_x - prompt_x, font_size as i32) as u32
+            };
+
+            // Width available to a value, keeping clear of the scrollbar
+            let value_w = |element: &Element| -> u32 {
+                cmp::max(
+                    body_w as i32 - margin_lr * 2 - value_x(element),
+                    font_size as i32,
+                ) as u32
             };
 
-            let draw_options_box =
-                |display: &mut Display, x: i32, mut y: i32, element: &Element| {
-                    let mut w = 0;
-                    for option in element.options.iter() {
-                        w = cmp::max(w, option.prompt.width());
+            // Width of the labels drawn beside the options of an element, including their margin
+            let option_labels_w = |element: &Element| -> u32 {
+                let mut labels_w = 0;
+                for option in element.options.iter() {
+                    if let Some(label) = option.label() {
+                        let rendered = ui.render(&label, help_font_size);
+                        labels_w = cmp::max(labels_w, rendered.width() + margin_lr as u32);
                     }
+                }
+                labels_w
+            };
+
+            // Size of the list box of an ordered list or one-of popup, with long option names
+            // cut off to leave space for their labels within the value column
+            let options_box_size = |element: &Element| -> (u32, i32) {
+                let (above_opt, below_opt) = list_more_texts(element);
+
+                let mut w = 0;
+                for option in element.options.iter() {
+                    w = cmp::max(w, option.prompt.width());
+                }
+                let mut h = 0;
+                for text in above_opt.iter().chain(below_opt.iter()) {
+                    w = cmp::max(w, text.width());
+                    h += text.height() as i32 + margin_tb;
+                }
+                let max_w = value_w(element).saturating_sub(option_labels_w(element));
+                w = cmp::min(w, cmp::max(max_w, font_size as u32 * 4));
+                for option in &element.options[element.list_range(list_rows.get())] {
+                    h += option.prompt.height() as i32 + margin_tb;
+                }
+                (w, h - margin_tb)
+            };
+
+            let draw_options_box =
+                |display: &mut Display,
+                 x: i32,
+                 mut y: i32,
+                 element: &Element,
+                 hit_boxes: &mut Vec<HitBox>| {
+                    let (above_opt, below_opt) = list_more_texts(element);
+                    let (w, _) = options_box_size(element);
 
                     let start_y = y;
-                    for (i, option) in element.options.iter().enumerate() {
+                    if let Some(above) = above_opt {
+                        above.draw(display, x, y, ui.text_color);
+                        y += above.height() as i32 + margin_tb;
+                    }
+                    let range = element.list_range(list_rows.get());
+                    for (i, option) in element
+                        .options
+                        .iter()
+                        .enumerate()
+                        .take(range.end)
+                        .skip(range.start)
+                    {
                         let highlighted = i == element.list_i;
                         if highlighted && editing {
                             ui.draw_pretty_box(display, x, y, w, option.prompt.height(), true);
@@ -601,9 +2349,30 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                         } else {
                             ui.text_color
                         };
-                        option.prompt.draw(display, x, y, text_color);
+                        if option.prompt.width() > w {
+                            let (_line, clipped) = ui.render_clipped(&option.name, font_size, w);
+                            clipped.draw(display, x, y, text_color);
+                        } else {
+                            option.prompt.draw(display, x, y, text_color);
+                        }
+                        let option_h = option.prompt.height();
+                        if option.dimmed() {
+                            display.rect(x, y, w, option_h, ui.disabled_color);
+                        }
+                        hit_boxes.push(HitBox {
+                            index: i,
+                            x,
+                            y,
+                            w: w as i32,
+                            h: option_h as i32 + margin_tb,
+                        });
+                        draw_option_label(display, x + w as i32 + margin_lr, y, option);
                         y += option.prompt.height() as i32 + margin_tb;
                     }
+                    if let Some(below) = below_opt {
+                        below.draw(display, x, y, ui.text_color);
+                        y += below.height() as i32 + margin_tb;
+                    }
                     if y > start_y {
                         ui.draw_pretty_box(
                             display,
@@ -618,6 +2387,72 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                     y
                 };
 
+            // Lines of text cut off at `width`, or wrapped to show all of it when highlighted
+            let column_texts = |string: &str, width: u32, highlighted: bool| {
+                let mut texts = Vec::new();
+                for line in string.lines() {
+                    if highlighted {
+                        for (line, text) in ui.render_wrapped(line, font_size, width) {
+                            // Words too long for a line of their own are still cut off
+                            if text.width() > width {
+                                let (line, text) = ui.render_clipped(line, font_size, width);
+                                texts.push((line.as_str(), text));
+                            } else {
+                                texts.push((line.as_str(), text));
+                            }
+                        }
+                    } else {
+                        let (line, text) = ui.render_clipped(line, font_size, width);
+                        texts.push((line.as_str(), text));
+                    }
+                }
+                texts
+            };
+
+            // Height of an element as it will be drawn, not including margins
+            let element_height = |element: &Element, highlighted: bool| -> i32 {
+                // Heights only change with the highlight and the options shown in a list box
+                let range = if element.list {
+                    element.list_range(list_rows.get())
+                } else {
+                    0..0
+                };
+                if let Some((cached_highlighted, start, end, h)) = element.height_cache.get() {
+                    if cached_highlighted == highlighted && (start..end) == range {
+                        return h;
+                    }
+                }
+
+                let mut prompt_h = 0;
+                for (_line, text) in column_texts(&element.prompt, prompt_w(element), highlighted) {
+                    prompt_h += text.height() as i32;
+                }
+                if prompt_h == 0 {
+                    prompt_h = font_size as i32;
+                }
+
+                let mut value_h = 0;
+                if element.list {
+                    value_h = options_box_size(element).1;
+                } else if !element.editable && element.options.is_empty() {
+                    if let Some(ref text_two) = element.text_two_opt {
+                        for (_line, text) in column_texts(text_two, value_w(element), highlighted) {
+                            value_h += text.height() as i32;
+                        }
+                    }
+                }
+
+                let h = if stacked && element.has_value() {
+                    prompt_h + margin_tb + cmp::max(value_h, font_size as i32)
+                } else {
+                    cmp::max(prompt_h, value_h)
+                };
+                element
+                    .height_cache
+                    .set(Some((highlighted, range.start, range.end, h)));
+                h
+            };
+
             let mut y = margin_tb;
             let mut bottom_y = display_h as i32;
 
@@ -625,13 +2460,31 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                 .get(selected)
                 .map(|e| (e.list, e.options.is_empty()))
                 .unwrap_or((false, false));
+            let editing_adjustable = elements
+                .get(selected)
+                .is_some_and(|e| e.numeric_opt.is_some() || e.date_time_opt.is_some());
+            let editing_toggles = elements.get(selected).is_some_and(|e| e.list_toggles());
 
             // Draw header
+            if !search.is_empty() {
+                let matched = elements.iter().any(|e| e.matches_search(&search));
+                let search_text = if matched {
+                    format!("Search: {search}")
+                } else {
+                    format!("Search: {search} (no matches)")
+                };
+                let rendered = ui.render(&search_text, help_font_size);
+                rendered.draw(display, margin_lr, y, ui.highlight_color);
+            }
+            if form.SettingChangedFlag {
+                let rendered = ui.render("Unsaved Changes", help_font_size);
+                let x = display_w as i32 - rendered.width() as i32 - margin_lr;
+                rendered.draw(display, x, y, ui.highlight_color);
+            }
             if let Some(ref title) = title_opt {
-                // TODO: Do not render in drawing loop
-                let rendered = ui.font.render(title, title_font_size);
+                let rendered = ui.render(title, title_font_size);
                 let x = (display_w as i32 - rendered.width() as i32) / 2;
-                ui.draw_text_box(display, x, y, &rendered, false, false);
+                ui.draw_text_box(display, x, y, rendered, false, false);
                 y += rendered.height() as i32 + margin_tb;
             }
 
@@ -639,64 +2492,219 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
             y += margin_tb * 2;
 
             // Draw footer
+            let mut help_box_opt = None;
             {
-                let mut i = 0;
-                let mut render_hotkey_help = |help: &str| {
-                    let rendered = ui.font.render(help, help_font_size);
-                    let x = match i % 3 {
-                        0 => {
-                            bottom_y -= rendered.height() as i32 + margin_tb;
-                            (display_w as i32) * 2 / 3 + margin_lr
+                // Hotkey help is placed in up to three columns, as many as fit the widest help
+                let footer_columns = |helps: &[&str]| -> usize {
+                    let help_w = helps
+                        .iter()
+                        .map(|help| ui.render(help, help_font_size).width())
+                        .max()
+                        .unwrap_or(0);
+                    let columns = display_w / (help_w + margin_lr as u32 * 2);
+                    (columns as usize).clamp(1, 3)
+                };
+
+                let help = match elements.get(selected) {
+                    Some(element) => {
+                        let boot_opt = element
+                            .options
+                            .get(element.list_i)
+                            .and_then(|option| option.boot_opt.as_ref())
+                            .filter(|_| editing);
+                        match (&element.password_opt, boot_opt) {
+                            (Some(password), _) if editing => password.stage.prompt(),
+                            // Tell boot options apart by where they boot from
+                            (
+                                _,
+                                Some(ElementBoot {
+                                    device_path_opt: Some(device_path),
+                                    ..
+                                }),
+                            ) => device_path.as_str(),
+                            _ => element.help.as_str(),
                         }
-                        1 => (display_w as i32) / 3 + margin_lr,
-                        _ => margin_lr,
-                    };
-                    ui.draw_text_box(display, x, bottom_y, &rendered, false, false);
-                    i += 1;
+                    }
+                    None => "",
                 };
+                let help_w = if two_pane {
+                    help_pane_w - margin_lr as u32 * 4
+                } else {
+                    display_w - margin_lr as u32 * 4
+                };
+                let help_texts = ui.render_wrapped(help.trim(), help_font_size, help_w);
+
+                // Start from the top when different help is shown, or from the search match
+                if help != help_text {
+                    help_text = help.to_string();
+                    help_start = help_texts
+                        .iter()
+                        .position(|(line, _text)| search_match(line, &search).is_some())
+                        .unwrap_or(0);
+                }
 
+                let mut hotkey_helps_shown = Vec::new();
                 if editing {
-                    render_hotkey_help("Esc=Discard Changes");
+                    hotkey_helps_shown.push("Esc=Discard Changes");
+                } else if !search.is_empty() {
+                    hotkey_helps_shown.push("Esc=Clear Search");
                 } else if form.FormId == FRONT_PAGE_FORM_ID {
-                    render_hotkey_help("");
+                    hotkey_helps_shown.push("");
                 } else {
-                    render_hotkey_help("Esc=Exit");
+                    hotkey_helps_shown.push("Esc=Exit");
                 }
                 if selected == !0 {
-                    render_hotkey_help("");
+                    hotkey_helps_shown.push("");
                 } else if editing {
-                    render_hotkey_help("Enter=Save Changes");
+                    hotkey_helps_shown.push("Enter=Save Changes");
                 } else {
-                    render_hotkey_help("Enter=Select Entry");
+                    hotkey_helps_shown.push("Enter=Select Entry");
                 }
                 if selected == !0 {
-                    render_hotkey_help("");
+                    hotkey_helps_shown.push("");
                 } else if !editing || !editing_value {
-                    render_hotkey_help("↑↓=Move Highlight");
+                    hotkey_helps_shown.push("↑↓=Move Highlight");
+                } else if editing_adjustable {
+                    hotkey_helps_shown.push("↑↓=Change Value");
                 }
 
                 if editing {
                     if editing_list {
-                        render_hotkey_help("PgDn=Move Selection Down");
-                        render_hotkey_help("");
-                        render_hotkey_help("PgUp=Move Selection Up");
+                        hotkey_helps_shown.push("PgDn=Move Selection Down");
+                        if editing_toggles {
+                            hotkey_helps_shown.push("Space=Enable/Disable");
+                        }
+                        hotkey_helps_shown.push("PgUp=Move Selection Up");
                     }
                 } else {
+                    // Help lines that fit, assuming the footer has a row for the scroll hint
+                    let help_rows_fit = if two_pane {
+                        let mut footer_helps = hotkey_helps_shown.clone();
+                        footer_helps.push("PgUp/PgDn=Scroll Help");
+                        footer_helps.extend(hotkey_helps.iter().map(String::as_str));
+                        let footer_rows =
+                            footer_helps.len().div_ceil(footer_columns(&footer_helps));
+                        let footer_h = footer_rows as i32 * (help_font_size as i32 + margin_tb)
+                            + margin_tb * 3 / 2;
+                        cmp::max(
+                            (bottom_y - footer_h - margin_tb - y) / help_font_size as i32,
+                            1,
+                        ) as usize
+                    } else {
+                        help_rows
+                    };
+                    if help_texts.len() > help_rows_fit {
+                        hotkey_helps_shown.push("PgUp/PgDn=Scroll Help");
+                    }
                     for hotkey_help in hotkey_helps.iter() {
-                        render_hotkey_help(hotkey_help);
+                        hotkey_helps_shown.push(hotkey_help);
+                    }
+                }
+
+                let columns = footer_columns(&hotkey_helps_shown);
+                for (i, hotkey_help) in hotkey_helps_shown.iter().enumerate() {
+                    let rendered = ui.render(hotkey_help, help_font_size);
+                    let column = i % columns;
+                    if column == 0 {
+                        bottom_y -= rendered.height() as i32 + margin_tb;
                     }
+                    // Rows are filled from the right
+                    let x = (display_w as i32) * (columns - 1 - column) as i32 / columns as i32
+                        + margin_lr;
+                    ui.draw_text_box(display, x, bottom_y, rendered, false, false);
                 }
 
                 bottom_y -= margin_tb * 3 / 2;
                 display.rect(0, bottom_y, display_w, 1, Color::rgb(0xac, 0xac, 0xac));
 
-                if let Some(element) = elements.get(selected) {
-                    if !element.help.trim().is_empty() {
-                        let rendered = ui.font.render(&element.help, help_font_size);
-                        let x = (display_w as i32 - rendered.width() as i32) / 2;
-                        bottom_y -= rendered.height() as i32 + margin_tb;
-                        ui.draw_text_box(display, x, bottom_y, &rendered, false, false);
+                // The help pane fills the space beside the body, the footer panel has a limit
+                let help_rows_shown = if two_pane {
+                    cmp::max((bottom_y - margin_tb - y) / help_font_size as i32, 1) as usize
+                } else {
+                    help_rows
+                };
+                help_start = cmp::min(help_start, help_texts.len().saturating_sub(help_rows_shown));
+                let help_end = cmp::min(help_start + help_rows_shown, help_texts.len());
+
+                let pane_x = body_w as i32;
+                if two_pane {
+                    let separator_y = y - margin_tb * 2;
+                    display.rect(
+                        pane_x,
+                        separator_y,
+                        1,
+                        (bottom_y - separator_y) as u32,
+                        Color::rgb(0xac, 0xac, 0xac),
+                    );
+                }
+
+                if help_start < help_end {
+                    let help_h: i32 = help_texts[help_start..help_end]
+                        .iter()
+                        .map(|(_line, text)| text.height() as i32)
+                        .sum();
+                    let help_y = if two_pane {
+                        y
+                    } else {
+                        bottom_y -= help_h + margin_tb;
+                        bottom_y
+                    };
+
+                    let mut line_y = help_y;
+                    for (line, text) in help_texts[help_start..help_end].iter() {
+                        let x = if two_pane {
+                            pane_x + margin_lr
+                        } else {
+                            (display_w as i32 - text.width() as i32) / 2
+                        };
+                        ui.draw_text_box(display, x, line_y, text, false, false);
+                        if let Some((start, end)) = search_match(line, &search) {
+                            // Underline the matched text, like in prompts
+                            let match_x = ui.render(&line[..start], help_font_size).width() as i32;
+                            let match_w = ui.render(&line[start..end], help_font_size).width();
+                            display.rect(
+                                x + match_x,
+                                line_y + text.height() as i32 - scale,
+                                match_w,
+                                scale as u32,
+                                ui.highlight_color,
+                            );
+                        }
+                        line_y += text.height() as i32;
+                    }
+
+                    // Show that the help can be scrolled
+                    if help_start > 0 {
+                        let rendered = ui.render("↑", help_font_size);
+                        let x = display_w as i32 - margin_lr - rendered.width() as i32;
+                        ui.draw_text_box(display, x, help_y, rendered, false, false);
+                    }
+                    if help_end < help_texts.len() {
+                        let rendered = ui.render("↓", help_font_size);
+                        let x = display_w as i32 - margin_lr - rendered.width() as i32;
+                        let y = line_y - rendered.height() as i32;
+                        ui.draw_text_box(display, x, y, rendered, false, false);
+                    }
+
+                    help_box_opt = Some(if two_pane {
+                        HitBox {
+                            index: selected,
+                            x: pane_x,
+                            y: help_y,
+                            w: help_pane_w as i32,
+                            h: bottom_y - help_y,
+                        }
+                    } else {
+                        HitBox {
+                            index: selected,
+                            x: 0,
+                            y: help_y,
+                            w: display_w as i32,
+                            h: help_h,
+                        }
+                    });
 
+                    if !two_pane {
                         bottom_y -= margin_tb * 3 / 2;
                         display.rect(0, bottom_y, display_w, 1, Color::rgb(0xac, 0xac, 0xac));
                     }
@@ -704,115 +2712,492 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
             }
 
             // Draw body
-            let max_form_elements = ((bottom_y - y) / (font_size as i32 + margin_tb)) as usize;
-
-            if element_start > 0 {
-                // Draw up arrow to indicate more items above
-                let arrow = ui.font.render("↑", help_font_size);
-                ui.draw_text_box(
-                    display,
-                    (display_w - arrow.width()) as i32 - margin_lr,
-                    y,
-                    &arrow,
-                    false,
-                    false,
-                );
+            let body_y = y;
+            let body_bottom_y = bottom_y - margin_tb;
+
+            // Leave room for the lines counting options above and below, the popup margins,
+            // and the prompt when it is stacked over the list
+            let mut list_h =
+                body_bottom_y - body_y - (help_font_size as i32 + margin_tb) * 2 - margin_tb * 4;
+            if stacked {
+                list_h -= font_size as i32 + margin_tb;
+            }
+            let list_rows_fit = list_h / (font_size as i32 + margin_tb);
+            list_rows.set(list_rows_fit.clamp(1, list_rows_max as i32) as usize);
+
+            for element in elements.iter_mut() {
+                // Unordered options are only shown in a list while editing one-of questions
+                if !element.options.is_empty() {
+                    element.scroll_list(list_rows.get());
+                }
+            }
+            let heights: Vec<i32> = elements
+                .iter()
+                .enumerate()
+                .map(|(i, element)| element_height(element, i == selected))
+                .collect();
+            let rows_h = |range: ops::Range<usize>| -> i32 {
+                heights[range].iter().map(|h| h + margin_tb).sum()
+            };
+
+            // Scroll so the selected element is fully visible, or at the top if it is too tall
+            if selected < elements.len() {
+                if selected < element_start {
+                    element_start = selected;
+                }
+                while element_start < selected
+                    && body_y + rows_h(element_start..selected + 1) > bottom_y
+                {
+                    element_start += 1;
+                }
+            }
+            element_start = cmp::min(element_start, elements.len());
+            // Do not leave space below the last element when scrolled down
+            while element_start > 0
+                && body_y + rows_h(element_start - 1..elements.len()) <= bottom_y
+            {
+                element_start -= 1;
             }
 
-            for i in element_start..(element_start + max_form_elements) {
-                if let Some(element) = elements.get(i) {
-                    let highlighted = i == selected;
-                    // TODO: Do not render in drawing loop
-                    let mut h = 0;
-                    for line in element.prompt.lines() {
-                        let rendered = ui.font.render(line, font_size);
+            let mut popup_opt = None;
+            let mut element_boxes = Vec::new();
+            let mut option_boxes = Vec::new();
+            let mut element_end = element_start;
+            for (i, element) in elements.iter().enumerate().skip(element_start) {
+                if i > element_start && y + heights[i] > body_bottom_y {
+                    break;
+                }
+                element_end = i + 1;
+
+                let highlighted = i == selected;
+                let row_y = y;
+                let mut h = 0;
+                let prompt_highlighted = highlighted && !editing;
+                // Nested statements are indented under their parent
+                let prompt_x = margin_lr * (element.depth as i32 + 1);
+                if element.changed {
+                    // Mark changed values with a leading bullet
+                    let marker = ui.render("•", font_size);
+                    let marker_x = prompt_x - (margin_lr + marker.width() as i32) / 2;
+                    marker.draw(display, marker_x, y, ui.highlight_color);
+                }
+                for (line, rendered) in
+                    column_texts(&element.prompt, prompt_w(element), highlighted)
+                {
+                    if element.changed && !prompt_highlighted {
+                        rendered.draw(display, prompt_x, y + h, ui.highlight_color);
+                    } else {
                         ui.draw_text_box(
                             display,
-                            margin_lr,
+                            prompt_x,
                             y + h,
-                            &rendered,
-                            highlighted && !editing,
-                            highlighted && !editing,
+                            rendered,
+                            prompt_highlighted,
+                            prompt_highlighted,
                         );
-                        h += rendered.height() as i32;
                     }
-                    if h == 0 {
-                        h = font_size as i32;
+                    if let Some((start, end)) = search_match(line, &search) {
+                        // Underline the matched text
+                        let match_x = ui.render(&line[..start], font_size).width() as i32;
+                        let match_w = ui.render(&line[start..end], font_size).width();
+                        let underline_color = if prompt_highlighted {
+                            ui.highlight_text_color
+                        } else {
+                            ui.highlight_color
+                        };
+                        display.rect(
+                            prompt_x + match_x,
+                            y + h + rendered.height() as i32 - scale,
+                            match_w,
+                            scale as u32,
+                            underline_color,
+                        );
                     }
+                    h += rendered.height() as i32;
+                }
+                if h == 0 {
+                    h = font_size as i32;
+                }
 
-                    let x = display_w as i32 / 2;
-                    if element.list {
-                        y = draw_options_box(display, x, y, element);
-                        y -= h + margin_tb;
-                    } else if let Some(option) =
-                        element.options.iter().find(|o| o.value == element.value)
-                    {
-                        ui.draw_text_box(
-                            display,
-                            x,
-                            y,
-                            &option.prompt,
-                            true,
-                            highlighted && editing,
-                        );
-                    } else if element.editable {
-                        draw_value_box(display, x, y, &element.value, highlighted && editing);
+                // Values are drawn in a column beside the prompts, or indented below them
+                let x = value_x(element);
+                let value_y = if stacked && element.has_value() {
+                    y + h + margin_tb
+                } else {
+                    y
+                };
+                if element.list {
+                    // Options can only be clicked while editing
+                    let options_y = if highlighted && editing {
+                        draw_options_box(display, x, value_y, element, &mut option_boxes)
+                    } else {
+                        draw_options_box(display, x, value_y, element, &mut Vec::new())
+                    };
+                    h = cmp::max(h, options_y - y - margin_tb);
+                } else if let Some(option) =
+                    element.options.iter().find(|o| o.value == element.value)
+                {
+                    // Long choices are cut off, unless the element is highlighted
+                    let clipped_opt = (!highlighted && option.prompt.width() > value_w(element))
+                        .then(|| {
+                            &ui.render_clipped(&option.name, font_size, value_w(element))
+                                .1
+                        });
+                    let rendered = clipped_opt.unwrap_or(&option.prompt);
+                    ui.draw_text_box(display, x, value_y, rendered, true, highlighted && editing);
+                    if highlighted && editing {
+                        // Choices are drawn over the following elements once the body is done
+                        popup_opt = Some((element, x, value_y, option.prompt.height() as i32));
                     }
+                } else if let Some(ref string) = element.string_opt {
+                    draw_string_box(
+                        display,
+                        x,
+                        value_y,
+                        string,
+                        value_w(element),
+                        highlighted && editing,
+                    );
+                } else if let Some(ref date_time) = element.date_time_opt {
+                    draw_date_time_box(display, x, value_y, date_time, highlighted && editing);
+                } else if let Some(ref numeric) = element.numeric_opt {
+                    draw_numeric_box(
+                        display,
+                        x,
+                        value_y,
+                        element,
+                        numeric,
+                        value_w(element),
+                        highlighted && editing,
+                    );
+                } else if element.editable {
+                    draw_value_box(display, x, value_y, element, highlighted && editing);
+                } else if let Some(ref text_two) = element.text_two_opt {
+                    let mut text_h = 0;
+                    for (_line, rendered) in column_texts(text_two, value_w(element), highlighted) {
+                        ui.draw_text_box(display, x, value_y + text_h, rendered, false, false);
+                        text_h += rendered.height() as i32;
+                    }
+                    h = cmp::max(h, value_y - y + text_h);
+                }
+                if value_y > y {
+                    h = cmp::max(h, value_y - y + font_size as i32);
+                }
 
-                    y += h + margin_tb;
+                y += h + margin_tb;
+
+                element_boxes.push(HitBox {
+                    index: i,
+                    x: 0,
+                    y: row_y - margin_tb / 2,
+                    w: body_w as i32,
+                    h: y - row_y,
+                });
+
+                if element.disabled {
+                    display.rect(
+                        0,
+                        row_y - margin_tb / 2,
+                        body_w,
+                        (y - row_y) as u32,
+                        ui.disabled_color,
+                    );
+                }
+                if element.locked {
+                    let lock_h = font_size as u32 * 3 / 4;
+                    let lock_y = value_y + (font_size as i32 - lock_h as i32) / 2;
+                    ui.draw_lock(display, x - margin_lr - lock_h as i32, lock_y, lock_h);
                 }
             }
 
-            if elements.len() > max_form_elements
-                && element_start < elements.len() - max_form_elements
-            {
-                // Draw down arrow to indicate more items below
-                let arrow = ui.font.render("↓", help_font_size);
-                ui.draw_text_box(
-                    display,
-                    (display_w - arrow.width()) as i32 - margin_lr,
-                    bottom_y - arrow.height() as i32 - margin_tb * 2,
-                    &arrow,
-                    false,
-                    false,
+            if let Some((element, x, value_y, value_h)) = popup_opt {
+                // Drop down the choices of a one-of question below its value, or above if
+                // there is no space
+                let (w, h) = options_box_size(element);
+                let labels_w = option_labels_w(element);
+                let mut popup_y = value_y + value_h + margin_tb * 3;
+                if popup_y + h > body_bottom_y {
+                    popup_y = cmp::max(value_y - margin_tb * 3 - h, body_y);
+                }
+                display.rect(
+                    x - margin_lr,
+                    popup_y - margin_tb * 2,
+                    w + labels_w + margin_lr as u32 * 2,
+                    (h + margin_tb * 4) as u32,
+                    ui.background_color,
+                );
+                draw_options_box(display, x, popup_y, element, &mut option_boxes);
+            }
+
+            if element_start > 0 || element_end < elements.len() {
+                // Draw scrollbar with a thumb proportional to the visible part of the form
+                let total_h = cmp::max(rows_h(0..elements.len()), 1);
+                let track_x = body_w as i32 - (margin_lr + scrollbar_w as i32) / 2;
+                let track_h = cmp::max(body_bottom_y - body_y, 0);
+                display.rect(
+                    track_x,
+                    body_y,
+                    scrollbar_w,
+                    track_h as u32,
+                    Color::rgba(0xac, 0xac, 0xac, 0x40),
+                );
+                let thumb_y = body_y + track_h * rows_h(0..element_start) / total_h;
+                let thumb_h = cmp::max(
+                    track_h * rows_h(element_start..element_end) / total_h,
+                    scrollbar_w as i32 * 2,
                 );
+                display.rect(
+                    track_x,
+                    thumb_y,
+                    scrollbar_w,
+                    thumb_h as u32,
+                    ui.outline_color,
+                );
+            }
+
+            if pointer.visible {
+                ui.draw_cursor(display, pointer.x, pointer.y, font_size as u32);
             }
 
             display.sync();
 
-            let signaled = wait_for_events(form)?;
+            #[cfg(feature = "frame_time")]
+            frame_timer.finish("frame");
+
+            // Text that was not drawn is rendered again if it is needed
+            ui.end_frame();
+
+            let signaled = wait_for_events(form, pointer)?;
             if signaled == EventType::Driver {
                 user_input.Action = BROWSER_ACTION_NONE;
                 break 'render;
             }
 
+            // Pointer actions that are handled like key presses
+            let mut pointer_keys = Vec::new();
+            for event in pointer.poll(display_w, display_h) {
+                match event {
+                    PointerEvent::Down { x, y } => {
+                        if let Some(option_box) = option_boxes.iter().find(|b| b.contains(x, y)) {
+                            if let Some(element) = elements.get_mut(selected) {
+                                element.list_i = option_box.index;
+                                if element.list {
+                                    dragging = true;
+                                } else {
+                                    // Choose from the one-of popup
+                                    element.value = element.options[option_box.index].value;
+                                    pointer_keys.push(Key::Enter);
+                                }
+                            }
+                        } else if editing {
+                            // Clicking outside of the one-of popup closes it
+                            if elements
+                                .get(selected)
+                                .is_some_and(|e| !e.list && !e.options.is_empty())
+                            {
+                                pointer_keys.push(Key::Escape);
+                            }
+                        } else if let Some(element_box) =
+                            element_boxes.iter().find(|b| b.contains(x, y))
+                        {
+                            // The first click highlights, the second selects
+                            if element_box.index == selected {
+                                pointer_keys.push(Key::Enter);
+                            } else if elements[element_box.index].selectable {
+                                selected = element_box.index;
+                            }
+                        }
+                    }
+                    PointerEvent::Drag { y, .. } => {
+                        if dragging {
+                            let target_opt = option_boxes
+                                .iter()
+                                .find(|b| y >= b.y && y < b.y + b.h)
+                                .map(|b| b.index);
+                            if let (Some(element), Some(target)) =
+                                (elements.get_mut(selected), target_opt)
+                            {
+                                // Move the dragged option right away, so following events in
+                                // this batch start from where it is now
+                                while element.list_i > target && element.move_list_option_up() {}
+                                while element.list_i < target && element.move_list_option_down() {}
+                            }
+                        }
+                    }
+                    PointerEvent::Up { .. } => {
+                        dragging = false;
+                    }
+                    PointerEvent::Scroll(direction) => {
+                        // Up and down change the value being edited, so the wheel is ignored
+                        if editing {
+                            continue;
+                        }
+                        // Scrolling over the help panel scrolls the help
+                        let over_help = !editing
+                            && help_box_opt
+                                .as_ref()
+                                .is_some_and(|b| b.contains(pointer.x, pointer.y));
+                        pointer_keys.push(match (over_help, direction < 0) {
+                            (true, true) => Key::PageUp,
+                            (true, false) => Key::PageDown,
+                            (false, true) => Key::Up,
+                            (false, false) => Key::Down,
+                        });
+                    }
+                }
+            }
+            let mut pointer_keys = pointer_keys.into_iter();
+
             // Consume all queued key presses
             'input: loop {
-                let raw_key = match raw_key(false) {
-                    Ok(ok) => ok,
-                    Err(err) => match err {
-                        Status::NOT_READY => break 'input,
-                        _ => return Err(err),
-                    },
+                let (key, modifiers) = match pointer_keys.next() {
+                    Some(key) => (key, Modifiers::default()),
+                    None => {
+                        let raw_key = match raw_key(false) {
+                            Ok(ok) => ok,
+                            Err(err) => match err {
+                                Status::NOT_READY => break 'input,
+                                _ => return Err(err),
+                            },
+                        };
+                        let modifiers = Modifiers::from_shift_state(raw_key.KeyState.KeyShiftState)
+                            .unwrap_or_default();
+
+                        if !editing {
+                            // Browser hotkeys are an EFI_INPUT_KEY, which has no shift state, so
+                            // they match the key whatever modifiers are held
+                            for hotkey in form.HotKeyListHead.iter() {
+                                let key_data = unsafe { &*hotkey.KeyData };
+                                if key_data.ScanCode == raw_key.Key.ScanCode
+                                    && key_data.UnicodeChar == raw_key.Key.UnicodeChar
+                                {
+                                    user_input.Action = hotkey.Action;
+                                    user_input.DefaultId = hotkey.DefaultId;
+                                    break 'render;
+                                }
+                            }
+                        }
+
+                        (Key::from(raw_key.Key), modifiers)
+                    }
+                };
+
+                // Characters typed with Ctrl or Alt are not text, so they do not reach text entry,
+                // search, or type-ahead
+                if matches!(key, Key::Character(_)) && (modifiers.ctrl || modifiers.alt) {
+                    continue 'input;
+                }
+
+                // Tab moves to the next element, Shift+Tab to the previous element or field
+                let key = match key {
+                    Key::Tab if modifiers.shift => {
+                        if editing {
+                            Key::Left
+                        } else {
+                            Key::Up
+                        }
+                    }
+                    Key::Tab if !editing => Key::Down,
+                    key => key,
                 };
 
-                if !editing {
-                    for hotkey in form.HotKeyListHead.iter() {
-                        let key_data = unsafe { &*hotkey.KeyData };
-                        if key_data.ScanCode == raw_key.ScanCode
-                            && key_data.UnicodeChar == raw_key.UnicodeChar
+                if editing {
+                    if let Some(element) = elements.get_mut(selected) {
+                        if element.input(&key) {
+                            continue 'input;
+                        }
+                        if let Key::Character(c) = key {
+                            if element.list && c == ' ' {
+                                if element.toggle_list_option() {
+                                    continue 'input;
+                                }
+                                // Keep editing, with the changes made so far
+                                dialog::message(
+                                    display,
+                                    &ui,
+                                    "This entry cannot be disabled",
+                                    None,
+                                )?;
+                                continue 'input;
+                            }
+                            if !element.list && !element.options.is_empty() {
+                                element.type_ahead(&mut type_ahead, c);
+                                continue 'input;
+                            }
+                        }
+                    }
+                } else {
+                    let search_changed = match key {
+                        Key::Character(c)
+                            if !c.is_control() && (c != ' ' || !search.is_empty()) =>
                         {
-                            user_input.Action = hotkey.Action;
-                            user_input.DefaultId = hotkey.DefaultId;
-                            break 'render;
+                            search.push(c);
+                            true
+                        }
+                        Key::Backspace if !search.is_empty() => {
+                            search.pop();
+                            true
+                        }
+                        Key::Escape if !search.is_empty() => {
+                            search.clear();
+                            continue 'input;
+                        }
+                        _ => false,
+                    };
+                    if search_changed {
+                        // Jump to the first match, starting from the highlighted statement
+                        let start = if selected < elements.len() {
+                            selected
+                        } else {
+                            0
+                        };
+                        for offset in 0..elements.len() {
+                            let i = (start + offset) % elements.len();
+                            if elements[i].selectable && elements[i].matches_search(&search) {
+                                selected = i;
+                                break;
+                            }
                         }
+                        continue 'input;
                     }
                 }
 
-                let key = Key::from(raw_key);
                 match key {
                     Key::Enter => {
                         if let Some(element) = elements.get_mut(selected) {
+                            if element.disabled {
+                                // Disabled statements cannot be changed or activated
+                                continue 'input;
+                            }
+                            if editing {
+                                if let EnterAction::Edit(message_opt) = element.finish_input() {
+                                    if let Some(message) = message_opt {
+                                        dialog::message(display, &ui, &message, None)?;
+                                    }
+                                    continue 'input;
+                                }
+                            }
+
+                            if element.password_opt.is_some() {
+                                match element.password_enter(form, editing, user_input)? {
+                                    EnterAction::Edit(message_opt) => {
+                                        if let Some(message) = message_opt {
+                                            dialog::message(display, &ui, &message, None)?;
+                                        }
+                                        editing = true;
+                                        continue 'input;
+                                    }
+                                    EnterAction::Exit(message_opt) => {
+                                        if let Some(message) = message_opt {
+                                            dialog::message(display, &ui, &message, None)?;
+                                        }
+                                        editing = false;
+                                        break 'display;
+                                    }
+                                    EnterAction::Submit => break 'render,
+                                }
+                            }
+
                             let mut checkbox = false;
                             {
                                 let statement = unsafe { &(*element.statement_ptr) };
@@ -841,10 +3226,34 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                     user_input.InputValue.Kind = kind;
                                     user_input.InputValue.Value = value;
 
+                                    let statement = unsafe { &(*element.statement_ptr) };
+                                    if let Some(error_info) =
+                                        validate_question(form, statement, &user_input.InputValue)
+                                    {
+                                        user_input.SelectedStatement = ptr::null();
+                                        let message =
+                                            string(error_info.StringId).unwrap_or_default();
+                                        dialog::message(
+                                            display,
+                                            &ui,
+                                            &message,
+                                            error_info.timeout_opt(),
+                                        )?;
+                                        continue 'input;
+                                    }
+
                                     break 'render;
                                 }
                             } else if element.editable && !editing {
+                                element.start_editing();
+                                type_ahead.clear();
                                 editing = true;
+                            } else if let Some(default_id) = element.default_id_opt {
+                                // Reset button restores the values of its default store
+                                user_input.SelectedStatement = element.statement_ptr;
+                                user_input.Action = BROWSER_ACTION_DEFAULT;
+                                user_input.DefaultId = default_id;
+                                break 'render;
                             } else {
                                 user_input.SelectedStatement = element.statement_ptr;
                                 unsafe {
@@ -855,46 +3264,32 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                     );
                                 }
                                 if editing {
-                                    if element.list {
-                                        let mut offset = 0;
-                                        if let Some(ref mut buffer) = element.buffer_opt {
-                                            for option in element.options.iter() {
-                                                macro_rules! copy_option {
-                                                    ($x:ident) => {{
-                                                        let next_offset =
-                                                            offset + mem::size_of_val(&$x);
-                                                        if next_offset <= buffer.len() {
-                                                            unsafe {
-                                                                ptr::copy(
-                                                                    &$x,
-                                                                    buffer.as_mut_ptr().add(offset)
-                                                                        as *mut _,
-                                                                    1,
-                                                                )
-                                                            }
-                                                        }
-                                                        offset = next_offset;
-                                                    }};
-                                                }
-                                                match option.value {
-                                                    IfrTypeValueEnum::U8(u8) => copy_option!(u8),
-                                                    IfrTypeValueEnum::U16(u16) => copy_option!(u16),
-                                                    IfrTypeValueEnum::U32(u32) => copy_option!(u32),
-                                                    IfrTypeValueEnum::U64(u64) => copy_option!(u64),
-                                                    _ => (),
-                                                }
-                                            }
-                                            if offset < buffer.len() {
-                                                for i in offset..buffer.len() {
-                                                    buffer[i] = 0;
-                                                }
-                                            }
-                                        }
-                                    } else {
-                                        let (kind, value) = unsafe { element.value.to_union() };
-                                        user_input.InputValue.Kind = kind;
-                                        user_input.InputValue.Value = value;
+                                    let original_opt =
+                                        element.store_input(&mut user_input.InputValue)?;
+
+                                    let statement = unsafe { &(*element.statement_ptr) };
+                                    if let Some(error_info) =
+                                        validate_question(form, statement, &user_input.InputValue)
+                                    {
+                                        // Undo changes and continue editing
+                                        element.undo_input(&user_input.InputValue, original_opt);
+                                        user_input.SelectedStatement = ptr::null();
+
+                                        let message =
+                                            string(error_info.StringId).unwrap_or_default();
+                                        dialog::message(
+                                            display,
+                                            &ui,
+                                            &message,
+                                            error_info.timeout_opt(),
+                                        )?;
+                                        continue 'input;
                                     }
+
+                                    for message in element.write_boot_options() {
+                                        dialog::message(display, &ui, &message, None)?;
+                                    }
+
                                     editing = false;
                                 }
                                 break 'render;
@@ -903,6 +3298,9 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                     }
                     Key::Escape => {
                         if editing {
+                            if let Some(element) = elements.get_mut(selected) {
+                                element.cancel_input(form);
+                            }
                             editing = false;
                             break 'display;
                         } else if form.FormId != FRONT_PAGE_FORM_ID {
@@ -911,30 +3309,7 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                         }
                     }
                     Key::Down => {
-                        if editing {
-                            if let Some(element) = elements.get_mut(selected) {
-                                if element.list {
-                                    if element.list_i + 1 < element.options.len() {
-                                        element.list_i += 1;
-                                    } else {
-                                        element.list_i = 0;
-                                    }
-                                } else {
-                                    let i_opt = element
-                                        .options
-                                        .iter()
-                                        .position(|o| o.value == element.value);
-                                    if let Some(mut i) = i_opt {
-                                        if i + 1 < element.options.len() {
-                                            i += 1;
-                                        } else {
-                                            i = 0;
-                                        }
-                                        element.value = element.options[i].value;
-                                    }
-                                }
-                            }
-                        } else if selected != !0 {
+                        if !editing && selected != !0 {
                             let start = selected;
                             loop {
                                 if selected + 1 < elements.len() {
@@ -951,39 +3326,14 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                     break;
                                 }
                             }
-                            if selected == 0 {
-                                // Handle wrapping
+                            if selected < start {
+                                // Handle wrapping, showing any statements above the selection
                                 element_start = 0;
-                            } else if selected - element_start >= max_form_elements {
-                                element_start = selected - max_form_elements + 1;
                             }
                         }
                     }
                     Key::Up => {
-                        if editing {
-                            if let Some(element) = elements.get_mut(selected) {
-                                if element.list {
-                                    if element.list_i > 0 {
-                                        element.list_i -= 1;
-                                    } else if !element.options.is_empty() {
-                                        element.list_i = element.options.len() - 1;
-                                    }
-                                } else {
-                                    let i_opt = element
-                                        .options
-                                        .iter()
-                                        .position(|o| o.value == element.value);
-                                    if let Some(mut i) = i_opt {
-                                        if i > 0 {
-                                            i -= 1;
-                                        } else {
-                                            i = element.options.len() - 1;
-                                        }
-                                        element.value = element.options[i].value;
-                                    }
-                                }
-                            }
-                        } else if selected != !0 {
+                        if !editing && selected != !0 {
                             let start = selected;
                             loop {
                                 if selected > 0 {
@@ -1000,36 +3350,21 @@ fn form_display_inner(form: &Form, user_input: &mut UserInput) -> Result<()> {
                                     break;
                                 }
                             }
-                            if selected <= element_start {
-                                element_start = selected;
-                            } else if selected == elements.len() - 1 {
-                                // Handle wrapping
-                                element_start = if selected >= max_form_elements {
-                                    selected - max_form_elements + 1
-                                } else {
-                                    0
-                                };
+                            if selected > start {
+                                // Handle wrapping, showing any statements below the selection
+                                element_start = elements.len();
                             }
                         }
                     }
                     Key::PageDown => {
-                        if editing {
-                            if let Some(element) = elements.get_mut(selected) {
-                                if element.list && element.list_i + 1 < element.options.len() {
-                                    element.options.swap(element.list_i, element.list_i + 1);
-                                    element.list_i += 1;
-                                }
-                            }
+                        if !editing {
+                            // Limited to the end of the help when it is drawn
+                            help_start += 1;
                         }
                     }
                     Key::PageUp => {
-                        if editing {
-                            if let Some(element) = elements.get_mut(selected) {
-                                if element.list && element.list_i > 0 {
-                                    element.list_i -= 1;
-                                    element.options.swap(element.list_i, element.list_i + 1);
-                                }
-                            }
+                        if !editing {
+                            help_start = help_start.saturating_sub(1);
                         }
                     }
                     _ => (),
@@ -1045,10 +3380,57 @@ extern "efiapi" fn form_display(form: &Form, user_input: &mut UserInput) -> Stat
     form_display_inner(form, user_input).into()
 }
 
-extern "efiapi" fn exit_display() {}
+extern "efiapi" fn exit_display() {
+    unsafe {
+        if !DISPLAY.is_null() {
+            // Leave the screen black for whatever runs next
+            let mut display = Box::from_raw(DISPLAY);
+            DISPLAY = ptr::null_mut();
+            display.set(Color::rgb(0, 0, 0));
+            display.sync();
+        }
+
+        if !POINTER.is_null() {
+            drop(Box::from_raw(POINTER));
+            POINTER = ptr::null_mut();
+        }
+
+        if !BOOT_PATH_TEXTS.is_null() {
+            drop(Box::from_raw(BOOT_PATH_TEXTS));
+            BOOT_PATH_TEXTS = ptr::null_mut();
+        }
+
+        // No Ui outlives a call to the form display engine
+        Ui::release();
+    }
+
+    let uefi = std::system_table();
+    // Light gray on black, the default console attribute
+    let _ = (uefi.ConsoleOut.SetAttribute)(uefi.ConsoleOut, 0x07);
+    let _ = (uefi.ConsoleOut.ClearScreen)(uefi.ConsoleOut);
+    let _ = (uefi.ConsoleOut.EnableCursor)(uefi.ConsoleOut, true);
+}
+
+fn confirm_data_change_inner() -> Result<u32> {
+    let display = display()?;
+    let ui = Ui::new()?;
+
+    let action = match dialog::choice(
+        display,
+        &ui,
+        "Changes have been made. Save the changes before leaving?",
+        &["Save", "Discard", "Cancel"],
+    )? {
+        Some(0) => BROWSER_ACTION_SUBMIT,
+        Some(1) => BROWSER_ACTION_DISCARD,
+        _ => BROWSER_ACTION_NONE,
+    };
+    Ok(action)
+}
 
 extern "efiapi" fn confirm_data_change() -> usize {
-    0
+    // Stay on the form if the dialog cannot be shown
+    confirm_data_change_inner().unwrap_or(BROWSER_ACTION_NONE) as usize
 }
 
 impl Fde {
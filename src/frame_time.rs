@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Frame time measurement, enabled with the `frame_time` feature. Times are written to the QEMU
+//! debug console, which `make qemu` shows on standard output.
+
+use core::arch::asm;
+use core::cmp;
+use core::fmt::{self, Write};
+
+/// I/O port of the QEMU debug console
+const DEBUGCON_PORT: u16 = 0x402;
+
+static mut CYCLES_PER_US: u64 = 0;
+
+struct DebugCon;
+
+impl Write for DebugCon {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for b in s.bytes() {
+            unsafe {
+                asm!(
+                    "out dx, al",
+                    in("dx") DEBUGCON_PORT,
+                    in("al") b,
+                    options(nomem, nostack, preserves_flags),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        asm!(
+            "rdtsc",
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags),
+        );
+    }
+    ((high as u64) << 32) | low as u64
+}
+
+/// Time stamp counter cycles per microsecond, measured once with a short stall
+fn cycles_per_us() -> u64 {
+    unsafe {
+        if CYCLES_PER_US == 0 {
+            let start = rdtsc();
+            let _ = (std::system_table().BootServices.Stall)(10_000);
+            CYCLES_PER_US = cmp::max((rdtsc() - start) / 10_000, 1);
+        }
+        CYCLES_PER_US
+    }
+}
+
+pub struct FrameTimer {
+    start: u64,
+}
+
+impl FrameTimer {
+    pub fn start() -> Self {
+        Self { start: rdtsc() }
+    }
+
+    /// Write the time since the timer was started to the debug console
+    pub fn finish(self, name: &str) {
+        let cycles = rdtsc() - self.start;
+        let us = cycles / cycles_per_us();
+        let _ = writeln!(DebugCon, "{}: {}.{:03} ms", name, us / 1000, us % 1000);
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::prelude::*;
+use std::uefi::hii::StringId;
+use std::uefi::hii::ifr::{IfrOpHeader, IfrQuestionHeader, IfrStatementHeader};
+
+// TODO: Move to uefi library {
+pub const IFR_END_OP: u8 = 0x29;
+pub const IFR_WARNING_IF_OP: u8 = 0x63;
+
+pub const IFR_FLAG_CALLBACK: u8 = 0x04;
+
+pub const IFR_OPTION_DEFAULT: u8 = 0x10;
+pub const IFR_OPTION_DEFAULT_MFG: u8 = 0x20;
+
+pub const IFR_NUMERIC_SIZE: u8 = 0x03;
+pub const IFR_NUMERIC_SIZE_1: u8 = 0x00;
+pub const IFR_NUMERIC_SIZE_2: u8 = 0x01;
+pub const IFR_NUMERIC_SIZE_4: u8 = 0x02;
+
+pub const IFR_DISPLAY: u8 = 0x30;
+pub const IFR_DISPLAY_INT_DEC: u8 = 0x00;
+pub const IFR_DISPLAY_UINT_HEX: u8 = 0x20;
+
+pub const QF_DATE_YEAR_SUPPRESS: u8 = 0x01;
+pub const QF_DATE_MONTH_SUPPRESS: u8 = 0x02;
+pub const QF_DATE_DAY_SUPPRESS: u8 = 0x04;
+
+pub const QF_TIME_HOUR_SUPPRESS: u8 = 0x01;
+pub const QF_TIME_MINUTE_SUPPRESS: u8 = 0x02;
+pub const QF_TIME_SECOND_SUPPRESS: u8 = 0x04;
+
+#[repr(C, packed)]
+pub struct IfrDate {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub Flags: u8,
+}
+
+/// Numeric opcode with the range data for a value of type `T`
+#[repr(C, packed)]
+pub struct IfrNumericData<T> {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub Flags: u8,
+    pub MinValue: T,
+    pub MaxValue: T,
+    pub Step: T,
+}
+
+#[repr(C, packed)]
+pub struct IfrPassword {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub MinSize: u16,
+    pub MaxSize: u16,
+}
+
+#[repr(C, packed)]
+pub struct IfrResetButton {
+    pub Header: IfrOpHeader,
+    pub Statement: IfrStatementHeader,
+    pub DefaultId: u16,
+}
+
+#[repr(C, packed)]
+pub struct IfrString {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub MinSize: u8,
+    pub MaxSize: u8,
+    pub Flags: u8,
+}
+
+#[repr(C, packed)]
+pub struct IfrText {
+    pub Header: IfrOpHeader,
+    pub Statement: IfrStatementHeader,
+    pub TextTwo: StringId,
+}
+
+#[repr(C, packed)]
+pub struct IfrTime {
+    pub Header: IfrOpHeader,
+    pub Question: IfrQuestionHeader,
+    pub Flags: u8,
+}
+
+#[repr(C, packed)]
+pub struct IfrWarningIf {
+    pub Header: IfrOpHeader,
+    pub Warning: StringId,
+    pub TimeOut: u8,
+}
+// } TODO: Move to uefi library
+
+/// Find the opcodes with a raw opcode value nested in the scope of `op`
+///
+/// Opcodes are compared as bytes, as a scope can contain opcodes not known by `IfrOpCode`.
+pub unsafe fn scope_opcodes(op: *const IfrOpHeader, opcode: u8) -> Vec<*const u8> {
+    let start = op as *const u8;
+    let mut found = Vec::new();
+    let mut ptr = start;
+    let mut depth = 0;
+    loop {
+        let (code, length_scope) = unsafe { (*ptr, *ptr.add(1)) };
+        if ptr != start && code == opcode {
+            found.push(ptr);
+        }
+
+        if length_scope & 0x80 != 0 {
+            depth += 1;
+        } else if code == IFR_END_OP {
+            depth -= 1;
+        }
+
+        let len = (length_scope & 0x7F) as usize;
+        if depth <= 0 || len == 0 {
+            break;
+        }
+        ptr = unsafe { ptr.add(len) };
+    }
+    found
+}
@@ -2,8 +2,89 @@
 
 use core::char;
 use std::prelude::*;
+use std::proto::Protocol;
 use std::uefi::text::TextInputKey;
 
+// TODO: Move to uefi library {
+pub const SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID: Guid = guid!("dd9e7534-7762-4698-8c14-f58517a625aa");
+
+pub const SHIFT_STATE_VALID: u32 = 0x8000_0000;
+pub const RIGHT_SHIFT_PRESSED: u32 = 0x0000_0001;
+pub const LEFT_SHIFT_PRESSED: u32 = 0x0000_0002;
+pub const RIGHT_CONTROL_PRESSED: u32 = 0x0000_0004;
+pub const LEFT_CONTROL_PRESSED: u32 = 0x0000_0008;
+pub const RIGHT_ALT_PRESSED: u32 = 0x0000_0010;
+pub const LEFT_ALT_PRESSED: u32 = 0x0000_0020;
+pub const RIGHT_LOGO_PRESSED: u32 = 0x0000_0040;
+pub const LEFT_LOGO_PRESSED: u32 = 0x0000_0080;
+
+#[repr(C)]
+pub struct KeyState {
+    pub KeyShiftState: u32,
+    pub KeyToggleState: u8,
+}
+
+#[repr(C)]
+pub struct KeyData {
+    pub Key: TextInputKey,
+    pub KeyState: KeyState,
+}
+
+#[repr(C)]
+pub struct SimpleTextInputExProtocol {
+    pub Reset: extern "efiapi" fn(&SimpleTextInputExProtocol, ExtendedVerification: bool) -> Status,
+    pub ReadKeyStrokeEx:
+        extern "efiapi" fn(&SimpleTextInputExProtocol, KeyData: &mut KeyData) -> Status,
+    pub WaitForKeyEx: Event,
+    pub SetState: extern "efiapi" fn(&SimpleTextInputExProtocol, KeyToggleState: &u8) -> Status,
+    pub RegisterKeyNotify: extern "efiapi" fn(
+        &SimpleTextInputExProtocol,
+        KeyData: &KeyData,
+        KeyNotificationFunction: extern "efiapi" fn(KeyData: &KeyData) -> Status,
+        NotifyHandle: &mut usize,
+    ) -> Status,
+    pub UnregisterKeyNotify:
+        extern "efiapi" fn(&SimpleTextInputExProtocol, NotificationHandle: usize) -> Status,
+}
+
+pub struct SimpleTextInputEx(pub &'static mut SimpleTextInputExProtocol);
+
+impl Protocol<SimpleTextInputExProtocol> for SimpleTextInputEx {
+    fn guid() -> Guid {
+        SIMPLE_TEXT_INPUT_EX_PROTOCOL_GUID
+    }
+
+    fn new(inner: &'static mut SimpleTextInputExProtocol) -> Self {
+        SimpleTextInputEx(inner)
+    }
+}
+// } TODO: Move to uefi library
+
+/// Modifier keys held with a key press
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    /// Modifiers of a key shift state, or None if the state was not reported
+    pub fn from_shift_state(shift_state: u32) -> Option<Self> {
+        if shift_state & SHIFT_STATE_VALID == 0 {
+            return None;
+        }
+        let pressed = |left: u32, right: u32| shift_state & (left | right) != 0;
+        Some(Self {
+            shift: pressed(LEFT_SHIFT_PRESSED, RIGHT_SHIFT_PRESSED),
+            ctrl: pressed(LEFT_CONTROL_PRESSED, RIGHT_CONTROL_PRESSED),
+            alt: pressed(LEFT_ALT_PRESSED, RIGHT_ALT_PRESSED),
+            logo: pressed(LEFT_LOGO_PRESSED, RIGHT_LOGO_PRESSED),
+        })
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum Key {
@@ -74,29 +155,78 @@ impl From<TextInputKey> for Key {
     }
 }
 
-pub fn raw_key(wait: bool) -> Result<TextInputKey> {
+fn input_ex() -> Option<SimpleTextInputEx> {
+    let uefi = std::system_table();
+    SimpleTextInputEx::handle_protocol(uefi.ConsoleInHandle).ok()
+}
+
+/// Event signaled when a key press is available
+pub fn key_event() -> Event {
+    match input_ex() {
+        Some(input_ex) => input_ex.0.WaitForKeyEx,
+        None => std::system_table().ConsoleIn.WaitForKey,
+    }
+}
+
+/// Read a key press with its shift state, which is only valid if the console supports
+/// extended input
+pub fn raw_key(wait: bool) -> Result<KeyData> {
     let uefi = std::system_table();
 
     if wait {
         let mut index = 0;
         Result::from((uefi.BootServices.WaitForEvent)(
             1,
-            &uefi.ConsoleIn.WaitForKey,
+            &key_event(),
             &mut index,
         ))?;
     }
 
-    let mut key = TextInputKey {
-        ScanCode: 0,
-        UnicodeChar: 0,
+    let mut key_data = KeyData {
+        Key: TextInputKey {
+            ScanCode: 0,
+            UnicodeChar: 0,
+        },
+        KeyState: KeyState {
+            KeyShiftState: 0,
+            KeyToggleState: 0,
+        },
     };
 
-    Result::from((uefi.ConsoleIn.ReadKeyStroke)(uefi.ConsoleIn, &mut key))?;
+    match input_ex() {
+        Some(input_ex) => {
+            Result::from((input_ex.0.ReadKeyStrokeEx)(input_ex.0, &mut key_data))?;
 
-    Ok(key)
+            // Report Ctrl+letter as the letter, as some consoles give the control character.
+            // Backspace, Tab, and Enter share their control characters with Ctrl+H, Ctrl+I,
+            // and Ctrl+M, so those are left alone
+            let ctrl = Modifiers::from_shift_state(key_data.KeyState.KeyShiftState)
+                .is_some_and(|modifiers| modifiers.ctrl);
+            if ctrl
+                && (1..=26).contains(&key_data.Key.UnicodeChar)
+                && !matches!(key_data.Key.UnicodeChar, 0x08 | 0x09 | 0x0D)
+            {
+                key_data.Key.UnicodeChar += b'a' as u16 - 1;
+            }
+        }
+        None => {
+            Result::from((uefi.ConsoleIn.ReadKeyStroke)(
+                uefi.ConsoleIn,
+                &mut key_data.Key,
+            ))?;
+        }
+    }
+
+    Ok(key_data)
+}
+
+pub fn key_modifiers(wait: bool) -> Result<(Key, Modifiers)> {
+    let key_data = raw_key(wait)?;
+    let modifiers = Modifiers::from_shift_state(key_data.KeyState.KeyShiftState);
+    Ok((Key::from(key_data.Key), modifiers.unwrap_or_default()))
 }
 
 pub fn key(wait: bool) -> Result<Key> {
-    let raw_key = raw_key(wait)?;
-    Ok(Key::from(raw_key))
+    let (key, _modifiers) = key_modifiers(wait)?;
+    Ok(key)
 }
@@ -4,6 +4,7 @@
 #![no_main]
 #![allow(non_snake_case)]
 
+extern crate alloc;
 #[macro_use]
 extern crate memoffset;
 #[macro_use]
@@ -13,11 +14,17 @@ use std::prelude::*;
 
 use core::ptr;
 
+mod boot;
+mod dialog;
 mod display;
 mod fde;
+#[cfg(feature = "frame_time")]
+mod frame_time;
 mod hii;
+mod ifr;
 pub mod image;
 mod key;
+mod pointer;
 mod rng;
 mod security;
 mod ui;
@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use core::cmp;
+use std::prelude::*;
+use std::proto::Protocol;
+
+// TODO: Move to uefi library {
+pub const SIMPLE_POINTER_PROTOCOL_GUID: Guid = guid!("31878c87-0b75-11d5-9a4f-0090273fc14d");
+pub const ABSOLUTE_POINTER_PROTOCOL_GUID: Guid = guid!("8d59d32b-c655-4ae9-9b15-f25904992a43");
+
+const ABSP_TOUCH_ACTIVE: u32 = 0x0000_0001;
+
+#[repr(C)]
+pub struct SimplePointerMode {
+    pub ResolutionX: u64,
+    pub ResolutionY: u64,
+    pub ResolutionZ: u64,
+    pub LeftButton: bool,
+    pub RightButton: bool,
+}
+
+#[repr(C)]
+pub struct SimplePointerState {
+    pub RelativeMovementX: i32,
+    pub RelativeMovementY: i32,
+    pub RelativeMovementZ: i32,
+    pub LeftButton: bool,
+    pub RightButton: bool,
+}
+
+#[repr(C)]
+pub struct SimplePointerProtocol {
+    pub Reset: extern "efiapi" fn(&SimplePointerProtocol, ExtendedVerification: bool) -> Status,
+    pub GetState:
+        extern "efiapi" fn(&SimplePointerProtocol, State: &mut SimplePointerState) -> Status,
+    pub WaitForInput: Event,
+    pub Mode: &'static SimplePointerMode,
+}
+
+pub struct SimplePointer(pub &'static mut SimplePointerProtocol);
+
+impl Protocol<SimplePointerProtocol> for SimplePointer {
+    fn guid() -> Guid {
+        SIMPLE_POINTER_PROTOCOL_GUID
+    }
+
+    fn new(inner: &'static mut SimplePointerProtocol) -> Self {
+        SimplePointer(inner)
+    }
+}
+
+#[repr(C)]
+pub struct AbsolutePointerMode {
+    pub AbsoluteMinX: u64,
+    pub AbsoluteMinY: u64,
+    pub AbsoluteMinZ: u64,
+    pub AbsoluteMaxX: u64,
+    pub AbsoluteMaxY: u64,
+    pub AbsoluteMaxZ: u64,
+    pub Attributes: u32,
+}
+
+#[repr(C)]
+pub struct AbsolutePointerState {
+    pub CurrentX: u64,
+    pub CurrentY: u64,
+    pub CurrentZ: u64,
+    pub ActiveButtons: u32,
+}
+
+#[repr(C)]
+pub struct AbsolutePointerProtocol {
+    pub Reset: extern "efiapi" fn(&AbsolutePointerProtocol, ExtendedVerification: bool) -> Status,
+    pub GetState:
+        extern "efiapi" fn(&AbsolutePointerProtocol, State: &mut AbsolutePointerState) -> Status,
+    pub WaitForInput: Event,
+    pub Mode: &'static AbsolutePointerMode,
+}
+
+pub struct AbsolutePointer(pub &'static mut AbsolutePointerProtocol);
+
+impl Protocol<AbsolutePointerProtocol> for AbsolutePointer {
+    fn guid() -> Guid {
+        ABSOLUTE_POINTER_PROTOCOL_GUID
+    }
+
+    fn new(inner: &'static mut AbsolutePointerProtocol) -> Self {
+        AbsolutePointer(inner)
+    }
+}
+// } TODO: Move to uefi library
+
+/// Pixels moved per millimeter of relative pointer movement
+const PIXELS_PER_MM: i64 = 4;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PointerEvent {
+    /// Button pressed or touch started
+    Down { x: i32, y: i32 },
+    /// Moved while the button is held
+    Drag { x: i32, y: i32 },
+    /// Button released or touch ended
+    Up { x: i32, y: i32 },
+    /// Wheel turned, negative values scroll up
+    Scroll(i32),
+}
+
+/// All pointing devices, tracking a single cursor
+pub struct Pointer {
+    simple: Vec<SimplePointer>,
+    absolute: Vec<AbsolutePointer>,
+    pub x: i32,
+    pub y: i32,
+    pressed: bool,
+    /// Set once a pointer is used, so the cursor is not drawn for keyboard only users
+    pub visible: bool,
+}
+
+impl Pointer {
+    pub fn new(w: u32, h: u32) -> Self {
+        let simple = SimplePointer::all();
+        for pointer in simple.iter() {
+            let _ = (pointer.0.Reset)(pointer.0, false);
+        }
+        let absolute = AbsolutePointer::all();
+        for pointer in absolute.iter() {
+            let _ = (pointer.0.Reset)(pointer.0, false);
+        }
+        Self {
+            simple,
+            absolute,
+            x: w as i32 / 2,
+            y: h as i32 / 2,
+            pressed: false,
+            visible: false,
+        }
+    }
+
+    /// Events signaled when any pointing device has input
+    pub fn events(&self) -> Vec<Event> {
+        let mut events = Vec::new();
+        events.extend(self.simple.iter().map(|pointer| pointer.0.WaitForInput));
+        events.extend(self.absolute.iter().map(|pointer| pointer.0.WaitForInput));
+        events
+    }
+
+    fn update(&mut self, x: i32, y: i32, pressed: bool, events: &mut Vec<PointerEvent>) {
+        let moved = x != self.x || y != self.y;
+        self.x = x;
+        self.y = y;
+        self.visible = true;
+        if pressed && !self.pressed {
+            events.push(PointerEvent::Down { x, y });
+        } else if !pressed && self.pressed {
+            events.push(PointerEvent::Up { x, y });
+        } else if pressed && moved {
+            events.push(PointerEvent::Drag { x, y });
+        }
+        self.pressed = pressed;
+    }
+
+    /// Read the state of all pointing devices, with the cursor kept inside a `w` by `h` display
+    pub fn poll(&mut self, w: u32, h: u32) -> Vec<PointerEvent> {
+        let max_x = w as i32 - 1;
+        let max_y = h as i32 - 1;
+        let mut events = Vec::new();
+
+        let mut simple_states = Vec::new();
+        for pointer in self.simple.iter() {
+            let mut state = SimplePointerState {
+                RelativeMovementX: 0,
+                RelativeMovementY: 0,
+                RelativeMovementZ: 0,
+                LeftButton: false,
+                RightButton: false,
+            };
+            if (pointer.0.GetState)(pointer.0, &mut state).is_success() {
+                simple_states.push((
+                    state,
+                    cmp::max(pointer.0.Mode.ResolutionX, 1) as i64,
+                    cmp::max(pointer.0.Mode.ResolutionY, 1) as i64,
+                ));
+            }
+        }
+        for (state, resolution_x, resolution_y) in simple_states {
+            let dx = state.RelativeMovementX as i64 * PIXELS_PER_MM / resolution_x;
+            let dy = state.RelativeMovementY as i64 * PIXELS_PER_MM / resolution_y;
+            let x = (self.x + dx as i32).clamp(0, max_x);
+            let y = (self.y + dy as i32).clamp(0, max_y);
+            self.update(x, y, state.LeftButton, &mut events);
+            if state.RelativeMovementZ != 0 {
+                events.push(PointerEvent::Scroll(state.RelativeMovementZ.signum()));
+            }
+        }
+
+        let mut absolute_states = Vec::new();
+        for pointer in self.absolute.iter() {
+            let mut state = AbsolutePointerState {
+                CurrentX: 0,
+                CurrentY: 0,
+                CurrentZ: 0,
+                ActiveButtons: 0,
+            };
+            if (pointer.0.GetState)(pointer.0, &mut state).is_success() {
+                let mode = pointer.0.Mode;
+                let scale = |value: u64, min: u64, max: u64, size: u32| -> i32 {
+                    let range = cmp::max(max.saturating_sub(min), 1);
+                    (value.saturating_sub(min) * size as u64 / range) as i32
+                };
+                absolute_states.push((
+                    scale(state.CurrentX, mode.AbsoluteMinX, mode.AbsoluteMaxX, w),
+                    scale(state.CurrentY, mode.AbsoluteMinY, mode.AbsoluteMaxY, h),
+                    state.ActiveButtons & ABSP_TOUCH_ACTIVE != 0,
+                ));
+            }
+        }
+        for (x, y, pressed) in absolute_states {
+            self.update(x.clamp(0, max_x), y.clamp(0, max_y), pressed, &mut events);
+        }
+
+        events
+    }
+}
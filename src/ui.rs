@@ -1,4 +1,6 @@
-use core::ptr;
+use alloc::collections::BTreeMap;
+use core::cell::UnsafeCell;
+use core::{cmp, mem, ptr};
 
 use orbclient::{Color, Renderer};
 use orbfont::{Font, Text};
@@ -15,6 +17,82 @@ static CHECKBOX_UNCHECKED_BMP: &[u8] = include_bytes!("../res/checkbox_unchecked
 static mut FONT: *const Font = ptr::null_mut();
 static mut CHECKBOX_CHECKED: *const Image = ptr::null_mut();
 static mut CHECKBOX_UNCHECKED: *const Image = ptr::null_mut();
+// Held by one Ui at a time, and kept here while there is none
+static mut TEXT_CACHE: *mut TextCache = ptr::null_mut();
+
+/// Frames that cached text is kept for after it was last used
+const TEXT_CACHE_FRAMES: u32 = 256;
+
+/// How cached text was laid out
+#[derive(Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+enum TextLayout {
+    /// A single line
+    Line,
+    /// A single line cut off at a width
+    Clipped(u32),
+    /// Lines wrapped at a width
+    Wrapped(u32),
+}
+
+struct CachedText {
+    // Boxed separately so references stay valid while more text is cached
+    lines: *mut Vec<(String, Text<'static>)>,
+    frame: u32,
+}
+
+impl Drop for CachedText {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.lines) });
+    }
+}
+
+/// Rendered text kept between frames and calls to the form display engine, so only text that
+/// changed has to be laid out and rendered again
+struct TextCache {
+    frame: u32,
+    // Keyed by layout and font size first, so strings can be looked up without copying them
+    texts: UnsafeCell<BTreeMap<(TextLayout, u32), BTreeMap<String, CachedText>>>,
+}
+
+impl TextCache {
+    fn new() -> Self {
+        Self {
+            frame: 0,
+            texts: UnsafeCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Lines of `string` laid out by `render`, which is only called if they are not cached
+    fn lines(
+        &self,
+        string: &str,
+        font_size: f32,
+        layout: TextLayout,
+        render: impl FnOnce() -> Vec<(String, Text<'static>)>,
+    ) -> &[(String, Text<'static>)] {
+        // Entries are only removed by `evict`, which cannot be called while text is borrowed
+        let texts = unsafe { &mut *self.texts.get() };
+        let strings = texts.entry((layout, font_size.to_bits())).or_default();
+        if !strings.contains_key(string) {
+            let lines = Box::into_raw(Box::new(render()));
+            strings.insert(string.to_string(), CachedText { lines, frame: 0 });
+        }
+        let cached = strings.get_mut(string).unwrap();
+        cached.frame = self.frame;
+        unsafe { &*cached.lines }
+    }
+
+    /// Remove text that has not been used for `TEXT_CACHE_FRAMES` frames
+    fn evict(&mut self) {
+        let frame = self.frame;
+        let texts = self.texts.get_mut();
+        texts.retain(|_key, strings| {
+            strings.retain(|_string, cached| frame.wrapping_sub(cached.frame) < TEXT_CACHE_FRAMES);
+            !strings.is_empty()
+        });
+        self.frame = frame.wrapping_add(1);
+    }
+}
 
 pub struct Ui {
     pub background_color: Color,
@@ -22,9 +100,11 @@ pub struct Ui {
     pub outline_color: Color,
     pub text_color: Color,
     pub highlight_text_color: Color,
+    pub disabled_color: Color,
     pub font: &'static Font,
     checkbox_checked: &'static Image,
     checkbox_unchecked: &'static Image,
+    text_cache: *mut TextCache,
 }
 
 impl Ui {
@@ -34,6 +114,8 @@ impl Ui {
         let outline_color = Color::rgba(0xfe, 0xff, 0xff, 0xc4);
         let text_color = Color::rgb(0xCC, 0xCC, 0xCC);
         let highlight_text_color = Color::rgb(0x27, 0x27, 0x27);
+        // Background blended over disabled items to dim them
+        let disabled_color = Color::rgba(0x36, 0x32, 0x2F, 0xA0);
 
         let font = unsafe {
             if FONT.is_null() {
@@ -77,56 +159,195 @@ impl Ui {
             &*CHECKBOX_UNCHECKED
         };
 
+        let text_cache = unsafe {
+            if TEXT_CACHE.is_null() {
+                Box::into_raw(Box::new(TextCache::new()))
+            } else {
+                let text_cache = TEXT_CACHE;
+                TEXT_CACHE = ptr::null_mut();
+                text_cache
+            }
+        };
+
         Ok(Self {
             background_color,
             highlight_color,
             outline_color,
             text_color,
             highlight_text_color,
+            disabled_color,
             font,
             checkbox_checked,
             checkbox_unchecked,
+            text_cache,
         })
     }
 
+    /// Free the cached font, images, and text, which are loaded again by the next call to `new`
+    ///
+    /// # Safety
+    ///
+    /// No `Ui` or reference obtained from one may be used after this is called
+    pub unsafe fn release() {
+        unsafe {
+            if !FONT.is_null() {
+                drop(Box::from_raw(FONT as *mut Font));
+                FONT = ptr::null_mut();
+            }
+            if !CHECKBOX_CHECKED.is_null() {
+                drop(Box::from_raw(CHECKBOX_CHECKED as *mut Image));
+                CHECKBOX_CHECKED = ptr::null_mut();
+            }
+            if !CHECKBOX_UNCHECKED.is_null() {
+                drop(Box::from_raw(CHECKBOX_UNCHECKED as *mut Image));
+                CHECKBOX_UNCHECKED = ptr::null_mut();
+            }
+            if !TEXT_CACHE.is_null() {
+                drop(Box::from_raw(TEXT_CACHE));
+                TEXT_CACHE = ptr::null_mut();
+            }
+        }
+    }
+
+    fn text_cache(&self) -> &TextCache {
+        unsafe { &*self.text_cache }
+    }
+
+    /// Render text, reusing the text rendered for a previous frame if there is one
+    pub fn render(&self, string: &str, font_size: f32) -> &Text<'static> {
+        let lines = self
+            .text_cache()
+            .lines(string, font_size, TextLayout::Line, || {
+                vec![(string.to_string(), self.font.render(string, font_size))]
+            });
+        &lines[0].1
+    }
+
+    /// Like `render_text_clipped`, reusing the text of a previous frame if there is one
+    pub fn render_clipped(
+        &self,
+        string: &str,
+        font_size: f32,
+        width: u32,
+    ) -> &(String, Text<'static>) {
+        let layout = TextLayout::Clipped(width);
+        let lines = self.text_cache().lines(string, font_size, layout, || {
+            vec![self.render_text_clipped(string, font_size, width)]
+        });
+        &lines[0]
+    }
+
+    /// Like `render_lines_wrapped`, reusing the text of a previous frame if there is one
+    pub fn render_wrapped(
+        &self,
+        string: &str,
+        font_size: f32,
+        width: u32,
+    ) -> &[(String, Text<'static>)] {
+        let layout = TextLayout::Wrapped(width);
+        self.text_cache().lines(string, font_size, layout, || {
+            self.render_lines_wrapped(string, font_size, width)
+        })
+    }
+
+    /// Count a frame as drawn, forgetting text that has not been used for a while
+    pub fn end_frame(&mut self) {
+        unsafe { &mut *self.text_cache }.evict();
+    }
+
     //TODO: move to orbfont and optimize
     pub fn render_text_wrapped(&self, string: &str, font_size: f32, width: u32) -> Vec<Text> {
-        let mut texts = Vec::new();
-
-        //TODO: support different whitespace differently, like newline?
-        let words: Vec<&str> = string.split_whitespace().collect();
+        self.render_lines_wrapped(string, font_size, width)
+            .into_iter()
+            .map(|(_line, text)| text)
+            .collect()
+    }
 
-        let mut line = String::new();
-        let mut last_text_opt = None;
-        let mut i = 0;
-        while i < words.len() {
-            if !line.is_empty() {
-                line.push(' ');
+    /// Like `render_text_wrapped`, also returning the string of each line
+    pub fn render_lines_wrapped(
+        &self,
+        string: &str,
+        font_size: f32,
+        width: u32,
+    ) -> Vec<(String, Text<'static>)> {
+        let mut lines = Vec::new();
+
+        // Each line is wrapped separately, so embedded newlines start a new line
+        for paragraph in string.lines() {
+            //TODO: support other whitespace differently, like tabs?
+            let words: Vec<&str> = paragraph.split_whitespace().collect();
+
+            // Keep blank lines, which separate paragraphs
+            if words.is_empty() {
+                lines.push((String::new(), self.font.render("", font_size)));
+                continue;
             }
-            line.push_str(words[i]);
-
-            let text = self.font.render(&line, font_size);
-            if text.width() > width {
-                line.clear();
-                if let Some(last_text) = last_text_opt.take() {
-                    texts.push(last_text);
-                    // Process this word again
-                    continue;
+
+            let mut line = String::new();
+            let mut last_opt = None;
+            let mut i = 0;
+            while i < words.len() {
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(words[i]);
+
+                let text = self.font.render(&line, font_size);
+                if text.width() > width {
+                    if let Some(last) = last_opt.take() {
+                        line.clear();
+                        lines.push(last);
+                        // Process this word again
+                        continue;
+                    } else {
+                        lines.push((mem::take(&mut line), text));
+                    }
                 } else {
-                    texts.push(text);
+                    last_opt = Some((line.clone(), text));
                 }
-            } else {
-                last_text_opt = Some(text);
+
+                i += 1;
             }
 
-            i += 1;
+            if let Some(last) = last_opt.take() {
+                lines.push(last);
+            }
         }
 
-        if let Some(last_text) = last_text_opt.take() {
-            texts.push(last_text);
+        lines
+    }
+
+    /// Render a single line, cut off with an ellipsis if it is wider than `width`. The string
+    /// that was rendered is returned with the text.
+    //TODO: move to orbfont and optimize
+    pub fn render_text_clipped(
+        &self,
+        string: &str,
+        font_size: f32,
+        width: u32,
+    ) -> (String, Text<'static>) {
+        let text = self.font.render(string, font_size);
+        if text.width() <= width {
+            return (string.to_string(), text);
+        }
+
+        // Find the most characters that fit along with the ellipsis
+        let indices: Vec<usize> = string.char_indices().map(|(i, _)| i).collect();
+        let clipped = |count: usize| format!("{}…", string[..indices[count]].trim_end());
+        let mut low = 0;
+        let mut high = indices.len() - 1;
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            if self.font.render(&clipped(mid), font_size).width() <= width {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
         }
 
-        texts
+        let string = clipped(low);
+        let text = self.font.render(&string, font_size);
+        (string, text)
     }
 
     pub fn draw_pretty_box(
@@ -309,4 +530,54 @@ impl Ui {
         checkbox.draw(display, x, y);
         checkbox.height() as i32
     }
+
+    /// Draw an arrow cursor of height `h` with its tip at the given position
+    pub fn draw_cursor(&self, display: &mut Display, x: i32, y: i32, h: u32) {
+        for row in 0..h {
+            let w = row / 2 + 1;
+            display.rect(x, y + row as i32, w, 1, self.highlight_text_color);
+            // Fill inside the outline
+            if row > 1 && row + 1 < h && w > 2 {
+                display.rect(x + 1, y + row as i32, w - 2, 1, self.text_color);
+            }
+        }
+    }
+
+    /// Draw a padlock fitting in a square of size `h`, returning its width
+    pub fn draw_lock(&self, display: &mut Display, x: i32, y: i32, h: u32) -> i32 {
+        let w = h * 3 / 4;
+        let thickness = cmp::max(h / 8, 1);
+        let shackle_w = w - thickness * 2;
+        let shackle_h = h / 2;
+
+        // Shackle
+        let shackle_x = x + thickness as i32;
+        display.rect(shackle_x, y, shackle_w, thickness, self.text_color);
+        display.rect(shackle_x, y, thickness, shackle_h, self.text_color);
+        display.rect(
+            shackle_x + (shackle_w - thickness) as i32,
+            y,
+            thickness,
+            shackle_h,
+            self.text_color,
+        );
+
+        // Body
+        display.rect(x, y + shackle_h as i32, w, h - shackle_h, self.text_color);
+
+        w as i32
+    }
+}
+
+impl Drop for Ui {
+    fn drop(&mut self) {
+        unsafe {
+            // Keep text for the next Ui, unless the font it was rendered with was released
+            if TEXT_CACHE.is_null() && !FONT.is_null() {
+                TEXT_CACHE = self.text_cache;
+            } else {
+                drop(Box::from_raw(self.text_cache));
+            }
+        }
+    }
 }